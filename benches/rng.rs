@@ -0,0 +1,64 @@
+//! Compares the generators in `fractal::rng`.
+//! 
+//! Run with `cargo bench --bench rng`. For every generator it reports
+//! raw throughput, the time to render the same number of points, and how
+//! far the resulting image is from the ChaCha render. The difference is 
+//! the mean absolute difference of the renders normalized to their maxima, 
+//! so two ChaCha renders with different seeds give the noise floor to compare against.
+
+use std::time::Instant;
+
+use rand::RngCore;
+use RustFractal::{fractal::rng::RngKind, my_grid::{MyGrid, MyGreyImage}};
+
+const WORDS: usize = 50_000_000;
+const DIM: usize = 1024;
+const POINTS: usize = 20_000_000;
+
+fn render(kind: RngKind, seed: u64) -> (f64, Vec<f64>)
+{
+    let mut grid = MyGrid::<u32>::new(DIM, DIM);
+
+    let start = Instant::now();
+    grid.fractalize_with_rng(POINTS, kind.seeded(seed));
+    let secs = start.elapsed().as_secs_f64();
+
+    let img: MyGreyImage<u32> = grid.into();
+    let max = img.iter().copied().max().unwrap_or(0).max(1) as f64;
+    (secs, img.iter().map(|&p| p as f64 / max).collect())
+}
+
+fn mean_abs_diff(a: &[f64], b: &[f64]) -> f64
+{
+    a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum::<f64>() / a.len() as f64
+}
+
+fn main()
+{
+    let (_, reference) = render(RngKind::ChaCha, 1);
+
+    println!("{:<10} {:>14} {:>14} {:>16}", "generator", "Mwords/s", "render s", "diff vs chacha");
+
+    for kind in RngKind::ALL
+    {
+        let mut rng = kind.seeded(2);
+        let start = Instant::now();
+        let mut acc = 0_u64;
+        for _ in 0..WORDS
+        {
+            acc ^= rng.next_u64();
+        }
+        let words_per_sec = WORDS as f64 / start.elapsed().as_secs_f64();
+        std::hint::black_box(acc);
+
+        let (secs, img) = render(kind, 2);
+
+        println!(
+            "{:<10} {:>14.1} {:>14.3} {:>16.6}", 
+            kind.to_string(), 
+            words_per_sec / 1e6, 
+            secs, 
+            mean_abs_diff(&reference, &img)
+        );
+    }
+}
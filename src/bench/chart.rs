@@ -0,0 +1,355 @@
+//! SVG charts of benchmark reports.
+//!
+//! The charts are plain, self-contained SVG with no scripts or external
+//! fonts, so they can be attached to a PR or opened in any browser.
+
+use std::fmt::Write as _;
+
+use super::{Case, Report};
+use crate::render::Strategy;
+
+const WIDTH: f64 = 760.0;
+const HEIGHT: f64 = 480.0;
+/// Left, right, top and bottom, the right one holding the legend
+const MARGIN: [f64; 4] = [70.0, 210.0, 40.0, 55.0];
+const COLORS: [&str; 8] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#17becf"];
+
+/// One line of a chart
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series
+{
+    pub name: String,
+    /// Plotted in the order given
+    pub points: Vec<(f64, f64)>,
+    /// Drawn dashed without markers, for reference lines
+    pub reference: bool,
+}
+
+/// A line chart with optionally logarithmic axes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart
+{
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub x_log: bool,
+    pub y_log: bool,
+    pub series: Vec<Series>,
+}
+
+/// Mean time against points on log axes, one line per size, strategy and thread count
+pub fn time_vs_points(report: &Report) -> Chart
+{
+    let mut series: Vec<(Case, Series)> = vec![];
+    for m in &report.measurements
+    {
+        let c = m.case;
+        let key = Case { points: 0, ..c };
+        let point = (c.points as f64, m.summary.mean);
+
+        match series.iter_mut().find(|(k, _)| *k == key)
+        {
+            Some((_, s)) => s.points.push(point),
+            None =>
+            {
+                let name = format!("{}x{} {} x{}", c.width, c.height, c.strategy, c.threads);
+                series.push((key, Series { name, points: vec![point], reference: false }));
+            },
+        }
+    }
+
+    let mut series: Vec<Series> = series.into_iter().map(|(_, s)| s).collect();
+    for s in &mut series { s.points.sort_by(|a, b| a.0.total_cmp(&b.0)) }
+
+    Chart
+    {
+        title: "Render time".to_string(),
+        x_label: "points".to_string(),
+        y_label: "mean seconds".to_string(),
+        x_log: true,
+        y_log: true,
+        series,
+    }
+}
+
+/// Speedup against threads, one line per strategy and size at the largest point count timed for the size.
+/// The speedup is over the serial strategy at the same size and points, or over the fewest threads
+/// of the same strategy when serial was not timed. `None` if nothing has anything to compare with.
+pub fn speedup_vs_threads(report: &Report) -> Option<Chart>
+{
+    let mean_of = |case: &Case| report.measurements.iter().find(|m| m.case == *case).map(|m| m.summary.mean);
+
+    let mut series: Vec<(Case, Series)> = vec![];
+    for m in &report.measurements
+    {
+        let c = m.case;
+        let most_points = report.measurements.iter()
+            .filter(|o| (o.case.width, o.case.height) == (c.width, c.height))
+            .map(|o| o.case.points)
+            .max();
+        if most_points != Some(c.points) { continue }
+
+        let serial = Case { strategy: Strategy::Serial, threads: 1, ..c };
+        let fewest = report.measurements.iter()
+            .filter(|o| Case { threads: c.threads, ..o.case } == c)
+            .min_by_key(|o| o.case.threads)
+            .map(|o| o.case);
+
+        let Some(base) = mean_of(&serial).or_else(|| fewest.and_then(|f| mean_of(&f))) else { continue };
+        let point = (c.threads as f64, base / m.summary.mean);
+
+        let key = Case { threads: 0, ..c };
+        match series.iter_mut().find(|(k, _)| *k == key)
+        {
+            Some((_, s)) => s.points.push(point),
+            None =>
+            {
+                let name = format!("{}x{} {}", c.width, c.height, c.strategy);
+                series.push((key, Series { name, points: vec![point], reference: false }));
+            },
+        }
+    }
+
+    // a lone serial point at one thread over itself says nothing
+    if series.iter().all(|(k, s)| k.strategy == Strategy::Serial && s.points.len() < 2) { return None }
+
+    let mut series: Vec<Series> = series.into_iter().map(|(_, s)| s).collect();
+    for s in &mut series { s.points.sort_by(|a, b| a.0.total_cmp(&b.0)) }
+
+    let most = series.iter().flat_map(|s| &s.points).map(|p| p.0).fold(1.0, f64::max);
+    series.push(Series { name: "ideal".to_string(), points: vec![(1.0, 1.0), (most, most)], reference: true });
+
+    Some(Chart
+    {
+        title: "Parallel speedup".to_string(),
+        x_label: "threads".to_string(),
+        y_label: "speedup".to_string(),
+        x_log: false,
+        y_log: false,
+        series,
+    })
+}
+
+/// Linear or logarithmic mapping of data to pixels
+struct Axis
+{
+    log: bool,
+    min: f64,
+    max: f64,
+    /// Pixel positions of `min` and `max`
+    from: f64,
+    to: f64,
+}
+
+impl Axis
+{
+    fn new(values: impl Iterator<Item = f64>, log: bool, from: f64, to: f64) -> Self
+    {
+        let values: Vec<f64> = values.filter(|v| v.is_finite() && (!log || *v > 0.0)).collect();
+        let (mut min, mut max) = values.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        if values.is_empty() { (min, max) = (1.0, 10.0) }
+
+        if log
+        {
+            // whole decades
+            (min, max) = (10_f64.powf(min.log10().floor()), 10_f64.powf(max.log10().ceil()));
+            if min == max { max = min * 10.0 }
+        }
+        else
+        {
+            let step = nice_step(max - min.min(0.0));
+            (min, max) = ((min.min(0.0) / step).floor() * step, (max / step).ceil() * step);
+            if min == max { max = min + 1.0 }
+        }
+
+        Axis { log, min, max, from, to }
+    }
+
+    fn map(&self, v: f64) -> f64
+    {
+        let t = if self.log { (v.log10() - self.min.log10()) / (self.max.log10() - self.min.log10()) }
+            else { (v - self.min) / (self.max - self.min) };
+        self.from + t * (self.to - self.from)
+    }
+
+    fn ticks(&self) -> Vec<f64>
+    {
+        if self.log
+        {
+            let (lo, hi) = (self.min.log10().round() as i32, self.max.log10().round() as i32);
+            let mut ticks: Vec<f64> = (lo..=hi).map(|e| 10_f64.powi(e)).collect();
+            // a decade or two needs more than its ends
+            if hi - lo <= 2
+            {
+                ticks = (lo..hi).flat_map(|e| [1.0, 2.0, 5.0].map(|m| m * 10_f64.powi(e))).chain([self.max]).collect();
+            }
+            ticks
+        }
+        else
+        {
+            let step = nice_step(self.max - self.min);
+            let n = ((self.max - self.min) / step).round() as usize;
+            (0..=n).map(|i| self.min + i as f64 * step).collect()
+        }
+    }
+}
+
+/// 1, 2 or 5 times a power of ten, giving about five steps over `range`
+fn nice_step(range: f64) -> f64
+{
+    if range <= 0.0 || !range.is_finite() { return 1.0 }
+
+    let rough = range / 5.0;
+    let power = 10_f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * power).find(|&s| s >= rough).unwrap_or(10.0 * power)
+}
+
+/// Short labels like `1e6`, `250`, `0.05`
+fn label(v: f64) -> String
+{
+    if v != 0.0 && (v.abs() >= 1e5 || v.abs() < 1e-3)
+    {
+        let e = v.abs().log10().floor() as i32;
+        let m = v / 10_f64.powi(e);
+        if (m - m.round()).abs() < 1e-9 { format!("{}e{e}", m.round()) } else { format!("{m:.1}e{e}") }
+    }
+    else
+    {
+        let s = format!("{v:.3}");
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+fn escape(text: &str) -> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Chart
+{
+    pub fn to_svg(&self) -> String
+    {
+        let [left, right, top, bottom] = MARGIN;
+        let points = || self.series.iter().flat_map(|s| &s.points);
+        let x = Axis::new(points().map(|p| p.0), self.x_log, left, WIDTH - right);
+        let y = Axis::new(points().map(|p| p.1), self.y_log, HEIGHT - bottom, top);
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="12">"#
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+        let _ = writeln!(svg, r#"<text x="{}" y="24" font-size="16" text-anchor="middle">{}</text>"#, (left + WIDTH - right) / 2.0, escape(&self.title));
+
+        // grid and ticks
+        for t in x.ticks()
+        {
+            let px = x.map(t);
+            let _ = writeln!(svg, r##"<line x1="{px:.1}" y1="{top}" x2="{px:.1}" y2="{}" stroke="#ddd"/>"##, HEIGHT - bottom);
+            let _ = writeln!(svg, r#"<text x="{px:.1}" y="{}" text-anchor="middle">{}</text>"#, HEIGHT - bottom + 16.0, label(t));
+        }
+        for t in y.ticks()
+        {
+            let py = y.map(t);
+            let _ = writeln!(svg, r##"<line x1="{left}" y1="{py:.1}" x2="{}" y2="{py:.1}" stroke="#ddd"/>"##, WIDTH - right);
+            let _ = writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"#, left - 6.0, py + 4.0, label(t));
+        }
+        let _ = writeln!(
+            svg, r#"<rect x="{left}" y="{top}" width="{}" height="{}" fill="none" stroke="black"/>"#,
+            WIDTH - left - right, HEIGHT - top - bottom
+        );
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, (left + WIDTH - right) / 2.0, HEIGHT - 12.0, escape(&self.x_label));
+        let _ = writeln!(
+            svg, r#"<text x="18" y="{0}" text-anchor="middle" transform="rotate(-90 18 {0})">{1}</text>"#,
+            (top + HEIGHT - bottom) / 2.0, escape(&self.y_label)
+        );
+
+        // lines, markers and legend
+        for (i, s) in self.series.iter().enumerate()
+        {
+            let color = if s.reference { "#888" } else { COLORS[i % COLORS.len()] };
+            let dash = if s.reference { r#" stroke-dasharray="6 4""# } else { "" };
+
+            let path: Vec<String> = s.points.iter()
+                .filter(|p| p.0.is_finite() && p.1.is_finite() && (!x.log || p.0 > 0.0) && (!y.log || p.1 > 0.0))
+                .map(|&(a, b)| format!("{:.1},{:.1}", x.map(a), y.map(b)))
+                .collect();
+
+            let _ = writeln!(svg, r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="2"{dash}/>"#, path.join(" "));
+            if !s.reference
+            {
+                for p in &path
+                {
+                    let (cx, cy) = p.split_once(',').unwrap_or(("0", "0"));
+                    let _ = writeln!(svg, r#"<circle cx="{cx}" cy="{cy}" r="3" fill="{color}"/>"#);
+                }
+            }
+
+            let ly = top + 10.0 + i as f64 * 18.0;
+            let lx = WIDTH - right + 12.0;
+            let _ = writeln!(svg, r#"<line x1="{lx}" y1="{ly}" x2="{}" y2="{ly}" stroke="{color}" stroke-width="2"{dash}/>"#, lx + 20.0);
+            let _ = writeln!(svg, r#"<text x="{}" y="{}">{}</text>"#, lx + 26.0, ly + 4.0, escape(&s.name));
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{label, speedup_vs_threads, time_vs_points};
+    use crate::{bench::{Case, Measurement, Report, Summary}, render::Strategy};
+
+    fn report(cases: &[(usize, Strategy, usize, f64)]) -> Report
+    {
+        Report
+        {
+            measurements: cases.iter().map(
+                |&(points, strategy, threads, secs)|
+                Measurement
+                {
+                    case: Case { width: 64, height: 64, points, strategy, threads },
+                    seconds: vec![secs],
+                    summary: Summary::of(&[secs], points),
+                }
+            ).collect()
+        }
+    }
+
+    #[test]
+    fn charts_have_a_line_per_series()
+    {
+        let r = report(&[
+            (1000, Strategy::Serial, 1, 1.0), (1000, Strategy::Parallel, 1, 1.0), (1000, Strategy::Parallel, 4, 0.5),
+            (10_000, Strategy::Serial, 1, 10.0), (10_000, Strategy::Parallel, 1, 10.0), (10_000, Strategy::Parallel, 4, 4.0),
+        ]);
+
+        let times = time_vs_points(&r);
+        assert_eq!(times.series.len(), 3);
+        assert_eq!(times.series[0].points, vec![(1000.0, 1.0), (10_000.0, 10.0)]);
+
+        let speedup = speedup_vs_threads(&r).unwrap();
+        let parallel = speedup.series.iter().find(|s| s.name == "64x64 parallel").unwrap();
+        assert_eq!(parallel.points, vec![(1.0, 1.0), (4.0, 2.5)]);
+        assert!(speedup.series.last().unwrap().reference);
+
+        let svg = speedup.to_svg();
+        assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<polyline").count(), speedup.series.len());
+
+        assert!(speedup_vs_threads(&report(&[(1000, Strategy::Serial, 1, 1.0)])).is_none());
+    }
+
+    #[test]
+    fn labels_stay_short()
+    {
+        assert_eq!(label(1_000_000.0), "1e6");
+        assert_eq!(label(2_500_000.0), "2.5e6");
+        assert_eq!(label(250.0), "250");
+        assert_eq!(label(0.05), "0.05");
+        assert_eq!(label(0.0), "0");
+    }
+}
@@ -0,0 +1,211 @@
+//! Telling real slowdowns from noise between two benchmark reports.
+//!
+//! The timings of each case in both reports go through Welch's t-test.
+//! A case is only called slower or faster when the difference is both
+//! significant and larger than a minimum relative change, since with
+//! enough repetitions even a meaningless difference becomes significant.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Case, Report, Summary};
+
+/// When a difference counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Significance
+{
+    /// Largest two-sided p-value taken as significant
+    pub alpha: f64,
+    /// Smallest relative change of the mean time worth reporting
+    pub min_change: f64,
+}
+
+impl Default for Significance
+{
+    fn default() -> Self
+    {
+        Significance { alpha: 0.05, min_change: 0.02 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict
+{
+    Slower,
+    Faster,
+    Unchanged,
+    /// Fewer than two repetitions on one side, so there is no spread to test against
+    Inconclusive,
+}
+
+/// One case present in both reports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comparison
+{
+    pub case: Case,
+    pub base: Summary,
+    pub new: Summary,
+    /// `new / base - 1` of the mean times, positive when slower
+    pub change: f64,
+    pub p_value: Option<f64>,
+    pub verdict: Verdict,
+}
+
+/// Compare every case timed in both reports, in the order of `new`
+pub fn compare(base: &Report, new: &Report, significance: Significance) -> Vec<Comparison>
+{
+    new.measurements
+    .iter()
+    .filter_map(
+        |n|
+        {
+            let b = base.measurements.iter().find(|b| b.case == n.case)?;
+
+            let change = n.summary.mean / b.summary.mean - 1.0;
+            let p_value = welch_p_value(&b.seconds, &n.seconds);
+
+            let verdict = match p_value
+            {
+                None => Verdict::Inconclusive,
+                Some(p) if p < significance.alpha && change >= significance.min_change => Verdict::Slower,
+                Some(p) if p < significance.alpha && change <= -significance.min_change => Verdict::Faster,
+                Some(_) => Verdict::Unchanged,
+            };
+
+            Some(Comparison { case: n.case, base: b.summary, new: n.summary, change, p_value, verdict })
+        }
+    )
+    .collect()
+}
+
+/// Two-sided p-value of Welch's t-test for equal means, `None` unless both samples have two values or more
+pub fn welch_p_value(a: &[f64], b: &[f64]) -> Option<f64>
+{
+    if a.len() < 2 || b.len() < 2 { return None }
+
+    let moments = |x: &[f64]|
+    {
+        let n = x.len() as f64;
+        let mean = x.iter().sum::<f64>() / n;
+        let var = x.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+        (n, mean, var / n)
+    };
+    let ((na, ma, sa), (nb, mb, sb)) = (moments(a), moments(b));
+
+    let se2 = sa + sb;
+    if se2 == 0.0 { return Some(if ma == mb { 1.0 } else { 0.0 }) }
+
+    let t = (mb - ma) / se2.sqrt();
+    let df = se2 * se2 / (sa * sa / (na - 1.0) + sb * sb / (nb - 1.0));
+
+    Some(incomplete_beta(df / 2.0, 0.5, df / (df + t * t)).clamp(0.0, 1.0))
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`, by its continued fraction
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64
+{
+    if x <= 0.0 { return 0.0 }
+    if x >= 1.0 { return 1.0 }
+
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+
+    // the fraction converges quickly on this side, the symmetry I_x(a, b) = 1 - I_{1-x}(b, a) covers the other
+    if x < (a + 1.0) / (a + b + 2.0) { front * beta_fraction(a, b, x) / a }
+    else { 1.0 - front * beta_fraction(b, a, 1.0 - x) / b }
+}
+
+/// Lentz's method, as in Numerical Recipes
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64
+{
+    const TINY: f64 = 1e-300;
+    let nonzero = |v: f64| if v.abs() < TINY { TINY } else { v };
+
+    let mut c = 1.0;
+    let mut d = 1.0 / nonzero(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+
+    for m in 1..300
+    {
+        let m = m as f64;
+
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / nonzero(1.0 + even * d);
+        c = nonzero(1.0 + even / c);
+        h *= d * c;
+
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / nonzero(1.0 + odd * d);
+        c = nonzero(1.0 + odd / c);
+        let step = d * c;
+        h *= step;
+
+        if (step - 1.0).abs() < 1e-15 { break }
+    }
+    h
+}
+
+/// Lanczos approximation, good to about 15 digits for positive `x`
+fn ln_gamma(x: f64) -> f64
+{
+    const G: f64 = 7.0;
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5
+    {
+        // reflection
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let sum = COEFFS[1..].iter().enumerate().fold(COEFFS[0], |s, (i, c)| s + c / (x + i as f64 + 1.0));
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{compare, welch_p_value, Significance, Verdict};
+    use crate::{bench::{Case, Measurement, Report, Summary}, render::Strategy};
+
+    #[test]
+    fn welch_matches_known_values()
+    {
+        // t = 2.228 at 10 degrees of freedom is the two-sided 5% point
+        let a = [1.0; 6];
+        assert_eq!(welch_p_value(&a, &a), Some(1.0));
+        assert_eq!(welch_p_value(&[1.0], &[2.0, 3.0]), None);
+
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let b = a.map(|v| v + 2.228 * (3.5_f64 / 3.0).sqrt());
+        let p = welch_p_value(&a, &b).unwrap();
+        assert!((p - 0.05).abs() < 1e-3, "{p}");
+    }
+
+    #[test]
+    fn only_significant_large_changes_are_flagged()
+    {
+        let case = |points| Case { width: 64, height: 64, points, strategy: Strategy::Serial, threads: 1 };
+        let report = |times: &[(usize, [f64; 4])]| Report
+        {
+            measurements: times.iter().map(|&(p, s)| Measurement { case: case(p), seconds: s.to_vec(), summary: Summary::of(&s, p) }).collect()
+        };
+
+        let base = report(&[(1, [1.00, 1.01, 0.99, 1.00]), (2, [1.0, 1.01, 0.99, 1.0]), (3, [1.0, 1.5, 0.5, 1.0]), (4, [1.0, 1.01, 0.99, 1.0])]);
+        let new = report(&[(1, [1.20, 1.21, 1.19, 1.20]), (2, [0.8, 0.81, 0.79, 0.8]), (3, [1.2, 1.7, 0.7, 1.2]), (4, [1.005, 1.015, 0.995, 1.005])]);
+
+        let verdicts: Vec<_> = compare(&base, &new, Significance::default()).into_iter().map(|c| c.verdict).collect();
+        assert_eq!(verdicts, [Verdict::Slower, Verdict::Faster, Verdict::Unchanged, Verdict::Unchanged]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Benchmark results kept across runs.
+//!
+//! Every recorded [`Run`] is a JSON file at `<dir>/<machine>/<revision>.json`,
+//! so timings are only ever compared with ones from the same machine, and
+//! recording a revision again replaces its earlier run.
+
+use std::{path::{Path, PathBuf}, process::Command, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use super::Report;
+
+/// Where the history lives unless told otherwise
+pub const DEFAULT_DIR: &str = "bench-history";
+
+/// What the timings were taken on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Machine
+{
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+}
+
+impl Machine
+{
+    /// The machine this runs on
+    pub fn current() -> Self
+    {
+        let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+        Machine
+        {
+            hostname,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        }
+    }
+
+    /// Name of the directory its runs go in, e.g. `desktop-linux-x86_64`
+    pub fn key(&self) -> String
+    {
+        sanitize(&format!("{}-{}-{}", self.hostname, self.os, self.arch))
+    }
+}
+
+/// The git revision of the working directory, with `-dirty` appended if it has
+/// uncommitted changes, or `None` outside a git checkout
+pub fn current_revision() -> Option<String>
+{
+    let git = |args: &[&str]|
+        Command::new("git").args(args).output().ok().filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+
+    let revision = git(&["rev-parse", "--short", "HEAD"])?;
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|s| !s.is_empty());
+
+    Some(if dirty { format!("{revision}-dirty") } else { revision })
+}
+
+/// A benchmark report with where and when it was taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Run
+{
+    pub machine: Machine,
+    pub revision: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub report: Report,
+}
+
+impl Run
+{
+    /// `report` as taken just now on this machine at the current revision
+    pub fn now(report: Report) -> Self
+    {
+        Run
+        {
+            machine: Machine::current(),
+            revision: current_revision().unwrap_or_else(|| "unknown".to_string()),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            report,
+        }
+    }
+
+    /// A run file, or a bare report written by `bench --json`, which gets an unknown machine and revision
+    pub fn load<Q>(path: Q) -> Result<Run, String>
+    where
+        Q: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+
+        serde_json::from_str::<Run>(&text)
+        .or_else(
+            |e|
+            {
+                let report: Report = serde_json::from_str(&text).map_err(|_| format!("{}: {e}", path.display()))?;
+                let unknown = "unknown".to_string();
+                let machine = Machine { hostname: unknown.clone(), os: unknown.clone(), arch: unknown.clone(), cpus: 0 };
+                Ok(Run { machine, revision: unknown, timestamp: 0, report })
+            }
+        )
+    }
+}
+
+/// A directory of recorded runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct History
+{
+    dir: PathBuf,
+}
+
+impl History
+{
+    pub fn new<Q>(dir: Q) -> Self
+    where
+        Q: Into<PathBuf>
+    {
+        History { dir: dir.into() }
+    }
+
+    fn path(&self, machine: &str, revision: &str) -> PathBuf
+    {
+        self.dir.join(sanitize(machine)).join(format!("{}.json", sanitize(revision)))
+    }
+
+    /// Store `run`, replacing any earlier run of the same revision on the same machine.
+    /// Returns where it went.
+    pub fn record(&self, run: &Run) -> std::io::Result<PathBuf>
+    {
+        let path = self.path(&run.machine.key(), &run.revision);
+        if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)? }
+
+        std::fs::write(&path, serde_json::to_string_pretty(run)?)?;
+        Ok(path)
+    }
+
+    pub fn load(&self, machine: &str, revision: &str) -> Result<Run, String>
+    {
+        Run::load(self.path(machine, revision))
+    }
+
+    /// Every run recorded on `machine`, oldest first
+    pub fn runs(&self, machine: &str) -> Result<Vec<Run>, String>
+    {
+        let dir = self.dir.join(sanitize(machine));
+        let entries = match std::fs::read_dir(&dir)
+        {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(format!("{}: {e}", dir.display())),
+        };
+
+        let mut runs = vec![];
+        for entry in entries
+        {
+            let path = entry.map_err(|e| format!("{}: {e}", dir.display()))?.path();
+            if path.extension().is_some_and(|e| e == "json") { runs.push(Run::load(path)?) }
+        }
+        runs.sort_by_key(|r| r.timestamp);
+        Ok(runs)
+    }
+}
+
+/// Keep names usable as file names
+fn sanitize(name: &str) -> String
+{
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{History, Machine, Run};
+    use crate::bench::Report;
+
+    #[test]
+    fn record_keys_runs_by_machine_and_revision()
+    {
+        let dir = std::env::temp_dir().join(format!("rf-history-{}", std::process::id()));
+        let history = History::new(&dir);
+
+        let machine = Machine { hostname: "box/1".to_string(), os: "linux".to_string(), arch: "x86_64".to_string(), cpus: 8 };
+        let run = |revision: &str, timestamp| Run { machine: machine.clone(), revision: revision.to_string(), timestamp, report: Report::default() };
+
+        history.record(&run("abc123", 2)).unwrap();
+        history.record(&run("def456", 1)).unwrap();
+        // recording a revision again replaces it
+        let path = history.record(&run("abc123", 3)).unwrap();
+        assert!(path.ends_with("box_1-linux-x86_64/abc123.json"));
+
+        assert_eq!(history.load(&machine.key(), "abc123").unwrap(), run("abc123", 3));
+        let revisions: Vec<_> = history.runs(&machine.key()).unwrap().into_iter().map(|r| r.revision).collect();
+        assert_eq!(revisions, ["def456", "abc123"]);
+        assert!(history.runs("elsewhere").unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
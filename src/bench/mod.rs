@@ -0,0 +1,288 @@
+//! Timing renders over a sweep of settings.
+//!
+//! A [`Sweep`] lists the grid sizes, point counts, strategies and thread
+//! counts to try. Every combination is rendered a few times and the
+//! timings are summarized in a [`Report`], which can be written as CSV,
+//! one row per case, or as JSON with the raw timings.
+
+pub mod chart;
+pub mod compare;
+pub mod history;
+
+use std::{fmt::Write as _, path::Path, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::FractalError, render::{RenderSettings, Strategy}};
+
+/// What to time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep
+{
+    /// `(width, height)` of each grid
+    pub sizes: Vec<(usize, usize)>,
+    pub points: Vec<usize>,
+    pub strategies: Vec<Strategy>,
+    /// Only swept for [`Strategy::Parallel`], the others always run on one thread
+    pub threads: Vec<usize>,
+    pub repetitions: usize,
+    /// Everything else about the renders
+    pub base: RenderSettings,
+}
+
+impl Default for Sweep
+{
+    fn default() -> Self
+    {
+        Sweep
+        {
+            sizes: vec![(1024, 1024)],
+            points: log_spaced(1_000_000, 100_000_000, 4),
+            strategies: vec![Strategy::Serial],
+            threads: vec![1],
+            repetitions: 3,
+            base: RenderSettings::default(),
+        }
+    }
+}
+
+/// `steps + 1` counts from `start` to about `end`, a constant factor apart, like `1e6, 1e7, 1e8` for two steps
+pub fn log_spaced(start: usize, end: usize, steps: usize) -> Vec<usize>
+{
+    if steps == 0 || start >= end { return vec![start] }
+
+    let factor = (end as f64 / start as f64).powf(1.0 / steps as f64);
+    (0..=steps).map(|i| (start as f64 * factor.powi(i as i32)).round() as usize).collect()
+}
+
+/// One combination of a sweep
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Case
+{
+    pub width: usize,
+    pub height: usize,
+    pub points: usize,
+    pub strategy: Strategy,
+    pub threads: usize,
+}
+
+/// Timings of one case
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Measurement
+{
+    pub case: Case,
+    /// Seconds taken by each repetition
+    pub seconds: Vec<f64>,
+    pub summary: Summary,
+}
+
+/// Statistics of the timings of a case, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Summary
+{
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation, zero for a single repetition
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Points over the median time
+    pub points_per_sec: f64,
+}
+
+impl Summary
+{
+    /// Panics if `seconds` is empty
+    pub fn of(seconds: &[f64], points: usize) -> Self
+    {
+        assert!(!seconds.is_empty(), "no timings to summarize");
+
+        let n = seconds.len() as f64;
+        let mean = seconds.iter().sum::<f64>() / n;
+        let stddev = if seconds.len() > 1
+        {
+            (seconds.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / (n - 1.0)).sqrt()
+        }
+        else { 0.0 };
+
+        let mut sorted = seconds.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] };
+
+        Summary
+        {
+            mean,
+            median,
+            stddev,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            points_per_sec: if median > 0.0 { points as f64 / median } else { f64::INFINITY },
+        }
+    }
+}
+
+/// Every measurement of a sweep
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Report
+{
+    pub measurements: Vec<Measurement>,
+}
+
+impl Sweep
+{
+    /// Every combination, sizes outermost and thread counts innermost
+    pub fn cases(&self) -> Vec<Case>
+    {
+        let mut cases = vec![];
+        for &(width, height) in &self.sizes
+        {
+            for &points in &self.points
+            {
+                for &strategy in &self.strategies
+                {
+                    let threads: &[usize] = if strategy == Strategy::Parallel { &self.threads } else { &[1] };
+                    for &threads in threads
+                    {
+                        cases.push(Case { width, height, points, strategy, threads });
+                    }
+                }
+            }
+        }
+        cases
+    }
+
+    /// Render every case `repetitions` times, telling `observer` about each case as it finishes.
+    /// Stops at the first case that fails to render.
+    pub fn run<F>(&self, mut observer: F) -> Result<Report, FractalError>
+    where
+        F: FnMut(&Measurement)
+    {
+        let mut report = Report::default();
+
+        for case in self.cases()
+        {
+            let settings = RenderSettings
+            {
+                rows: case.height,
+                cols: case.width,
+                points: Some(case.points),
+                strategy: case.strategy,
+                threads: case.threads,
+                ..self.base.clone()
+            };
+
+            let seconds: Vec<f64> = (0..self.repetitions.max(1))
+            .map(
+                |_|
+                {
+                    let start = Instant::now();
+                    let grid = settings.render::<u32>()?;
+                    let secs = start.elapsed().as_secs_f64();
+                    // dropping a big grid takes a while too, and is not the render's doing
+                    drop(grid);
+                    Ok(secs)
+                }
+            )
+            .collect::<Result<_, FractalError>>()?;
+
+            let m = Measurement { case, summary: Summary::of(&seconds, case.points), seconds };
+            observer(&m);
+            report.measurements.push(m);
+        }
+
+        Ok(report)
+    }
+}
+
+impl Report
+{
+    /// Header line, then one line per case
+    pub fn to_csv(&self) -> String
+    {
+        let mut csv = "width,height,points,strategy,threads,repetitions,mean_s,median_s,stddev_s,min_s,max_s,points_per_sec\n".to_string();
+        for m in &self.measurements
+        {
+            let (c, s) = (&m.case, &m.summary);
+            let _ = writeln!(
+                csv, "{},{},{},{},{},{},{},{},{},{},{},{}",
+                c.width, c.height, c.points, c.strategy, c.threads, m.seconds.len(),
+                s.mean, s.median, s.stddev, s.min, s.max, s.points_per_sec
+            );
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error>
+    {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn save_csv<Q>(&self, path: Q) -> std::io::Result<()>
+    where
+        Q: AsRef<Path>
+    {
+        std::fs::write(path, self.to_csv())
+    }
+
+    pub fn save_json<Q>(&self, path: Q) -> std::io::Result<()>
+    where
+        Q: AsRef<Path>
+    {
+        std::fs::write(path, self.to_json()?)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{log_spaced, Report, Summary, Sweep};
+    use crate::render::{RenderSettings, Strategy};
+
+    #[test]
+    fn log_spaced_hits_both_ends()
+    {
+        assert_eq!(log_spaced(1_000_000, 100_000_000, 2), vec![1_000_000, 10_000_000, 100_000_000]);
+        assert_eq!(log_spaced(1000, 1000, 3), vec![1000]);
+        assert_eq!(log_spaced(1000, 1_000_000, 0), vec![1000]);
+    }
+
+    #[test]
+    fn summary_statistics()
+    {
+        let s = Summary::of(&[1.0, 2.0, 3.0, 10.0], 100);
+        assert_eq!((s.mean, s.median, s.min, s.max), (4.0, 2.5, 1.0, 10.0));
+        assert!((s.stddev - 4.0825).abs() < 1e-4);
+        assert_eq!(s.points_per_sec, 40.0);
+
+        assert_eq!(Summary::of(&[2.0], 10).stddev, 0.0);
+    }
+
+    #[test]
+    fn sweep_times_every_case()
+    {
+        let sweep = Sweep
+        {
+            sizes: vec![(32, 16)],
+            points: vec![1000, 2000],
+            strategies: vec![Strategy::Serial, Strategy::Parallel],
+            threads: vec![1, 2],
+            repetitions: 2,
+            base: RenderSettings { seed: 1, ..Default::default() },
+        };
+
+        let mut seen = 0;
+        let report = sweep.run(|_| seen += 1).unwrap();
+        // serial once, parallel once per thread count
+        assert_eq!(seen, 2 * 3);
+        assert!(report.measurements.iter().all(|m| m.seconds.len() == 2));
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 1 + 6);
+        assert!(csv.lines().nth(1).unwrap().starts_with("32,16,1000,serial,1,2,"));
+
+        let json: Report = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        let cases = |r: &Report| r.measurements.iter().map(|m| m.case).collect::<Vec<_>>();
+        assert_eq!(cases(&json), cases(&report));
+    }
+}
@@ -0,0 +1,121 @@
+//! The error type of the rendering API.
+
+use std::fmt::Display;
+
+use crate::{
+    fractal::Index2DError,
+    my_grid::AllocError,
+    render::{checkpoint::CheckpointError, histogram::HistogramError, memory::MemoryError, scene::SceneError}
+};
+
+/// Anything that stops a render or keeps its results from being read or written
+#[derive(Debug)]
+pub enum FractalError
+{
+    /// A grid without pixels, or with more than can be addressed
+    InvalidDimensions { rows: usize, cols: usize },
+    /// A setting that cannot be rendered, named by its field
+    Invalid { field: String, message: String },
+    /// A pixel outside the grid
+    OutOfBounds(Index2DError),
+    Io(std::io::Error),
+    /// Encoding or saving an image
+    Image(image::ImageError),
+    Scene(SceneError),
+    Checkpoint(CheckpointError),
+    Histogram(HistogramError),
+    Memory(MemoryError),
+    /// Hits did not fit in their pixels with
+    /// [`Overflow::Fail`](crate::render::session::Overflow::Fail) set
+    Overflow { points: usize, clipped: u64 },
+    /// Cancelled before every point was plotted
+    Cancelled,
+}
+
+impl FractalError
+{
+    /// [`FractalError::Invalid`] from what a `validate` method returns
+    pub fn invalid(field: impl Into<String>, message: impl Into<String>) -> Self
+    {
+        FractalError::Invalid { field: field.into(), message: message.into() }
+    }
+}
+
+impl Display for FractalError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            FractalError::InvalidDimensions { rows, cols } => write!(f, "cannot render a {cols}x{rows} grid"),
+            FractalError::Invalid { field, message } => write!(f, "`{field}` {message}"),
+            FractalError::OutOfBounds(e) => write!(f, "{e}"),
+            FractalError::Io(e) => write!(f, "{e}"),
+            FractalError::Image(e) => write!(f, "image: {e}"),
+            FractalError::Scene(e) => write!(f, "{e}"),
+            FractalError::Checkpoint(e) => write!(f, "{e}"),
+            FractalError::Histogram(e) => write!(f, "{e}"),
+            FractalError::Memory(e) => write!(f, "{e}"),
+            FractalError::Overflow { points, clipped } =>
+                write!(f, "{clipped} hits overflowed their pixels after {points} points"),
+            FractalError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for FractalError
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self
+        {
+            FractalError::OutOfBounds(e) => Some(e),
+            FractalError::Io(e) => Some(e),
+            FractalError::Image(e) => Some(e),
+            FractalError::Scene(e) => Some(e),
+            FractalError::Checkpoint(e) => Some(e),
+            FractalError::Histogram(e) => Some(e),
+            FractalError::Memory(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_error {
+    ($($e:ty => $variant:ident),*) => {
+        $(
+            impl From<$e> for FractalError
+            {
+                fn from(value: $e) -> Self {
+                    FractalError::$variant(value)
+                }
+            }
+        )*
+    };
+}
+impl_from_error!(
+    Index2DError => OutOfBounds,
+    std::io::Error => Io,
+    image::ImageError => Image,
+    SceneError => Scene,
+    CheckpointError => Checkpoint,
+    HistogramError => Histogram
+);
+
+/// A grid too large to count is a matter of its dimensions, anything else of memory
+impl From<MemoryError> for FractalError
+{
+    fn from(value: MemoryError) -> Self {
+        match value
+        {
+            MemoryError::TooLarge { rows, cols } | MemoryError::Alloc(AllocError::TooLarge { rows, cols }) =>
+                FractalError::InvalidDimensions { rows, cols },
+            other => FractalError::Memory(other),
+        }
+    }
+}
+
+impl From<AllocError> for FractalError
+{
+    fn from(value: AllocError) -> Self {
+        MemoryError::from(value).into()
+    }
+}
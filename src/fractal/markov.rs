@@ -0,0 +1,299 @@
+//! Markov chain transform selection, as in "Markov IFS".
+//! 
+//! Instead of i.i.d. coin flips the probability of the next transform 
+//! depends on the one applied before it. Renders pick it up through
+//! [`RenderSettings::markov`](crate::render::RenderSettings::markov), and a [`Markov`]
+//! plugs into [`MyGrid::fractalize_with_choices`](crate::my_grid::MyGrid::fractalize_with_choices)
+//! like any other source of choices.
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::bits::BitStream;
+
+/// `matrix[i][j]` is the probability of applying transform `j` right after transform `i`,
+/// where index `0` is `false` (the polar map) and `1` is `true` (the rotation).
+pub type TransitionMatrix = [[f64; 2]; 2];
+
+#[derive(Debug, Clone)]
+pub struct Markov<R>
+{
+    matrix: TransitionMatrix,
+    prev: bool,
+    rng: R,
+}
+
+impl<R> Markov<R>
+where
+    R: RngCore
+{
+    /// Fails unless every entry is in `[0, 1]` and every row sums to one.
+    /// The first choice is drawn from the stationary distribution.
+    pub fn new(matrix: TransitionMatrix, mut rng: R) -> Result<Self, String>
+    {
+        validate(&matrix)?;
+        let prev = rng.gen::<f64>() >= stationary(&matrix)[0];
+        Ok(Markov { matrix, prev, rng })
+    }
+
+    /// Independent choices picking `true` with probability `p`,
+    /// i.e. a chain whose rows are identical.
+    pub fn weighted(p: f64, rng: R) -> Result<Self, String>
+    {
+        Markov::new([[1.0 - p, p], [1.0 - p, p]], rng)
+    }
+
+    pub fn matrix(&self) -> &TransitionMatrix
+    {
+        &self.matrix
+    }
+}
+
+/// Fails unless every entry is in `[0, 1]` and every row sums to one
+pub fn validate(matrix: &TransitionMatrix) -> Result<(), String>
+{
+    for (i, row) in matrix.iter().enumerate()
+    {
+        if let Some(p) = row.iter().find(|p| !(0.0..=1.0).contains(*p))
+        {
+            return Err(format!("transition probability {p} in row {i} is outside [0, 1]"));
+        }
+
+        let sum: f64 = row.iter().sum();
+        if (sum - 1.0).abs() > 1e-9
+        {
+            return Err(format!("row {i} of the transition matrix sums to {sum}, not 1"));
+        }
+    }
+    Ok(())
+}
+
+/// Long run fraction of steps spent on each transform.
+/// A chain that never leaves its first transform splits evenly.
+pub fn stationary(matrix: &TransitionMatrix) -> [f64; 2]
+{
+    let (to_true, to_false) = (matrix[0][1], matrix[1][0]);
+
+    if to_true + to_false == 0.0 { return [0.5, 0.5] }
+
+    let p_true = to_true / (to_true + to_false);
+    [1.0 - p_true, p_true]
+}
+
+impl<R> Iterator for Markov<R>
+where
+    R: RngCore
+{
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> 
+    {
+        let p_true = self.matrix[usize::from(self.prev)][1];
+        self.prev = self.rng.gen::<f64>() < p_true;
+        Some(self.prev)
+    }
+}
+
+/// Relative weights of the polar map and the rotation, in that order.
+/// Equal weights are the plain coin flip of [`Fractalize`](super::Fractalize).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Weights(pub [f64; 2]);
+
+impl Default for Weights
+{
+    fn default() -> Self 
+    {
+        Weights([1.0, 1.0])
+    }
+}
+
+impl Weights
+{
+    /// Fails unless both weights are finite and non-negative and at least one is positive
+    pub fn validate(&self) -> Result<(), String>
+    {
+        if self.0.iter().any(|w| !w.is_finite() || *w < 0.0)
+        {
+            return Err(format!("weights must be finite and non-negative, found {:?}", self.0));
+        }
+        if self.0.iter().sum::<f64>() <= 0.0
+        {
+            return Err("at least one weight must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn is_uniform(&self) -> bool
+    {
+        self.0[0] == self.0[1]
+    }
+
+    /// Probability of picking the rotation
+    pub fn p_rotation(&self) -> f64
+    {
+        self.0[1] / (self.0[0] + self.0[1])
+    }
+
+    /// `len` choices drawn from `rng` with these weights.
+    /// Uniform weights use the bits of `rng` directly.
+    pub fn choices<R>(&self, rng: R, len: usize) -> Choices<R>
+    where
+        R: RngCore
+    {
+        if self.is_uniform()
+        {
+            Choices::Uniform(BitStream::new(rng, len))
+        }
+        else
+        {
+            let chain = 
+                Markov::weighted(self.p_rotation(), rng)
+                .expect("weights are validated before use");
+            Choices::Chain(chain.take(len))
+        }
+    }
+}
+
+/// Where the transform choices of a render come from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChoiceSource
+{
+    /// Independent choices with these weights
+    Weights(Weights),
+    /// Each choice drawn from the row of the one before
+    Markov(TransitionMatrix),
+}
+
+impl Default for ChoiceSource
+{
+    fn default() -> Self 
+    {
+        ChoiceSource::Weights(Weights::default())
+    }
+}
+
+impl From<Weights> for ChoiceSource
+{
+    fn from(weights: Weights) -> Self 
+    {
+        ChoiceSource::Weights(weights)
+    }
+}
+
+impl ChoiceSource
+{
+    pub fn validate(&self) -> Result<(), String>
+    {
+        match self
+        {
+            ChoiceSource::Weights(w) => w.validate(),
+            ChoiceSource::Markov(m) => validate(m),
+        }
+    }
+
+    /// `len` choices drawn from `rng`
+    pub fn choices<R>(&self, rng: R, len: usize) -> Choices<R>
+    where
+        R: RngCore
+    {
+        match self
+        {
+            ChoiceSource::Weights(w) => w.choices(rng, len),
+            ChoiceSource::Markov(m) => 
+            {
+                let chain = Markov::new(*m, rng).expect("transition matrices are validated before use");
+                Choices::Chain(chain.take(len))
+            },
+        }
+    }
+}
+
+/// Iterator returned by [`Weights::choices`] and [`ChoiceSource::choices`]
+// Built once per render, boxing the bit buffer would only add an indirection per step
+#[allow(clippy::large_enum_variant)]
+pub enum Choices<R>
+{
+    Uniform(BitStream<R>),
+    Chain(std::iter::Take<Markov<R>>),
+}
+
+impl<R> Iterator for Choices<R>
+where
+    R: RngCore
+{
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> 
+    {
+        match self
+        {
+            Choices::Uniform(b) => b.next(),
+            Choices::Chain(m) => m.next(),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> 
+    {
+        match self
+        {
+            Choices::Uniform(b) => b.nth(n),
+            Choices::Chain(m) => m.nth(n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use rand::SeedableRng;
+
+    use super::{stationary, Markov, Weights};
+
+    fn rng() -> rand::rngs::StdRng
+    {
+        rand::rngs::StdRng::seed_from_u64(11)
+    }
+
+    #[test]
+    fn rejects_bad_matrices()
+    {
+        assert!(Markov::new([[0.5, 0.6], [0.5, 0.5]], rng()).is_err());
+        assert!(Markov::new([[1.5, -0.5], [0.5, 0.5]], rng()).is_err());
+        assert!(Markov::new([[0.2, 0.8], [0.7, 0.3]], rng()).is_ok());
+    }
+
+    #[test]
+    fn deterministic_chain_alternates()
+    {
+        let m = Markov::new([[0.0, 1.0], [1.0, 0.0]], rng()).unwrap();
+        let v: Vec<bool> = m.take(100).collect();
+        assert!(v.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn frequencies_approach_stationary()
+    {
+        let matrix = [[0.9, 0.1], [0.3, 0.7]];
+        assert_eq!(stationary(&matrix), [0.75, 0.25]);
+
+        let m = Markov::new(matrix, rng()).unwrap();
+        let n = 200_000;
+        let trues = m.take(n).filter(|&b| b).count() as f64 / n as f64;
+        assert!((trues - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn weights_pick_rotation_in_proportion()
+    {
+        let w = Weights([1.0, 3.0]);
+        assert_eq!(w.p_rotation(), 0.75);
+        assert!(Weights([0.0, 0.0]).validate().is_err());
+        assert!(Weights([-1.0, 2.0]).validate().is_err());
+
+        let n = 100_000;
+        let trues = w.choices(rng(), n).filter(|&b| b).count() as f64 / n as f64;
+        assert!((trues - 0.75).abs() < 0.01);
+        assert_eq!(Weights::default().choices(rng(), 77).count(), 77);
+    }
+}
@@ -1,6 +1,17 @@
+pub mod bits;
+pub mod markov;
+pub mod rng;
+pub mod sequence;
+
 use std::{f64::consts::PI, sync::{Mutex, MutexGuard}};
 
 use image::Pixel;
+use serde::{Deserialize, Serialize};
+
+use bits::BitStream;
+use rng::RngKind;
+
+use crate::error::FractalError;
 
 pub trait Index2D<Idx, Idy>
 where
@@ -21,7 +32,7 @@ where
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Index2DError
 where
     Self: Sized
@@ -29,9 +40,72 @@ where
     IndexOutOfBounds(String),
 }
 
+impl std::fmt::Display for Index2DError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            Index2DError::IndexOutOfBounds(e) => write!(f, "index out of bounds: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Index2DError {}
+
 pub trait Fractalize
 {
-    fn fractalize(&mut self, num_points: usize) -> ();
+    /// Plot `num_points` points of a fresh orbit.
+    /// Fails with [`FractalError::InvalidDimensions`] on an image without pixels.
+    fn fractalize(&mut self, num_points: usize) -> Result<(), FractalError>;
+}
+
+/// The pair of maps the orbit alternates between.
+///
+/// `true` picks the rotation by `rot`, `false` picks the
+/// polar to rectangular map shifted by `theta_offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transform
+{
+    pub rot: f64,
+    pub theta_offset: f64,
+}
+
+impl Default for Transform
+{
+    fn default() -> Self
+    {
+        Transform
+        {
+            rot: 1.724643921305295,
+            theta_offset: 3.0466792337230033,
+        }
+    }
+}
+
+impl Transform
+{
+    pub fn apply(&self, x: f64, y: f64, s: bool) -> (f64, f64)
+    {
+        if s
+        {
+            let (sin, cos) = self.rot.sin_cos();
+            (
+                x * cos + y * sin,
+                y * cos - x * sin
+            )
+        }
+        else
+        {
+            let rad = x * 0.5 + 0.5;
+            let theta = y * PI + self.theta_offset;
+            let (sin, cos) = theta.sin_cos();
+            (
+                rad * cos,
+                rad * sin
+            )
+        }
+    }
 }
 
 pub struct Image
@@ -43,6 +117,7 @@ pub struct Image
 
 impl std::fmt::Display for Image
 {
+    #[allow(clippy::write_with_newline)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Dimensions of image: x: {}, y: {}\n", self.x, self.y)?;
 
@@ -63,8 +138,13 @@ impl<P> Fractalize for image::ImageBuffer<image::Luma<P>, Vec<P> >
 where
     P: image::Primitive + num_traits::CheckedAdd,
 {
-    fn fractalize(&mut self, num_points: usize) -> () 
+    fn fractalize(&mut self, num_points: usize) -> Result<(), FractalError> 
     {
+        if self.is_empty()
+        {
+            return Err(FractalError::InvalidDimensions { rows: self.height() as usize, cols: self.width() as usize });
+        }
+
         let mut x: f64 = 0.0;
         let mut y: f64 = 0.5;
 
@@ -74,11 +154,9 @@ where
 
         // let mut rng = rand::thread_rng();
         
-        for _ in 0..num_points
+        for s in BitStream::new(RngKind::default().from_entropy(), num_points)
         {
-            let this_rand = rand::random::<u64>();
-
-            if this_rand & 1 == 1
+            if s
             {
                 (x, y) = (
                     x * rot.cos() + y * rot.sin(),
@@ -113,6 +191,7 @@ where
                 )
             }
         }
+        Ok(())
     }
 }
 
@@ -120,8 +199,17 @@ impl<P> Fractalize for Mutex<image::ImageBuffer<image::Luma<P>, Vec<P> > >
 where
     P: image::Primitive + num_traits::CheckedAdd 
 {
-    fn fractalize(&mut self, num_points: usize) -> () 
+    #[allow(clippy::mut_mutex_lock)]
+    fn fractalize(&mut self, num_points: usize) -> Result<(), FractalError> 
     {
+        {
+            let img = self.lock().unwrap();
+            if img.is_empty()
+            {
+                return Err(FractalError::InvalidDimensions { rows: img.height() as usize, cols: img.width() as usize });
+            }
+        }
+
         let mut x: f64 = 0.0;
         let mut y: f64 = 0.5;
 
@@ -131,11 +219,9 @@ where
 
         // let mut rng = rand::thread_rng();
         
-        for _ in 0..num_points
+        for s in BitStream::new(RngKind::default().from_entropy(), num_points)
         {
-            let this_rand = rand::random::<u64>();
-
-            if this_rand & 1 == 1
+            if s
             {
                 (x, y) = (
                     x * rot.cos() + y * rot.sin(),
@@ -173,6 +259,7 @@ where
                 )
             }
         }
+        Ok(())
     }
 }
 
@@ -182,6 +269,7 @@ where
 // {
 impl Image
 {
+    #[allow(clippy::slow_vector_initialization)]
     pub fn new(x: usize, y: usize) -> Image
     {
         let mut v = Vec::new();
@@ -200,11 +288,9 @@ impl Image
 
         // let mut rng = rand::thread_rng();
         
-        for _ in 0..num_pts
+        for s in BitStream::new(RngKind::default().from_entropy(), num_pts)
         {
-            let this_rand = rand::random::<u64>();
-
-            if this_rand & 1 == 1
+            if s
             {
                 (x, y) = (
                     x * rot.cos() + y * rot.sin(),
@@ -0,0 +1,351 @@
+//! Small, fast generators implemented in-crate.
+//! 
+//! Rendering is dominated by the generator and trig, and the orbit
+//! only needs one bit per point, so nothing here is cryptographic.
+//! Every generator implements [`RngCore`] and [`SeedableRng`] and can be
+//! handed to anything in the crate taking a generator, e.g. 
+//! [`MyGrid::fractalize_with_rng`](crate::my_grid::MyGrid::fractalize_with_rng).
+
+use std::{fmt::Display, str::FromStr};
+
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+fn fill_bytes_via_u64<R>(rng: &mut R, dest: &mut [u8]) -> ()
+where
+    R: RngCore + ?Sized
+{
+    for chunk in dest.chunks_mut(8)
+    {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// SplitMix64 by Steele, Lea and Flood.
+/// One add and a few multiply-xorshifts per output.
+#[derive(Debug, Clone)]
+pub struct SplitMix64
+{
+    state: u64,
+}
+
+impl SplitMix64
+{
+    pub fn new(state: u64) -> Self
+    {
+        SplitMix64 { state }
+    }
+}
+
+impl RngCore for SplitMix64
+{
+    fn next_u32(&mut self) -> u32 
+    {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 
+    {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) 
+    {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> 
+    {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for SplitMix64
+{
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self 
+    {
+        SplitMix64::new(u64::from_le_bytes(seed))
+    }
+
+    fn seed_from_u64(state: u64) -> Self 
+    {
+        SplitMix64::new(state)
+    }
+}
+
+/// xoshiro256** by Blackman and Vigna
+#[derive(Debug, Clone)]
+pub struct Xoshiro256StarStar
+{
+    s: [u64; 4],
+}
+
+impl Xoshiro256StarStar
+{
+    /// The all-zero state is a fixed point and is replaced
+    /// by the state seeded from zero.
+    pub fn new(s: [u64; 4]) -> Self
+    {
+        if s == [0; 4] { return Self::seed_from_u64(0) }
+        Xoshiro256StarStar { s }
+    }
+}
+
+impl RngCore for Xoshiro256StarStar
+{
+    fn next_u32(&mut self) -> u32 
+    {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 
+    {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) 
+    {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> 
+    {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Xoshiro256StarStar
+{
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: Self::Seed) -> Self 
+    {
+        let mut s = [0_u64; 4];
+        for (w, bytes) in s.iter_mut().zip(seed.chunks_exact(8))
+        {
+            *w = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Xoshiro256StarStar::new(s)
+    }
+
+    /// Expands the seed with SplitMix64, as recommended by the authors
+    fn seed_from_u64(state: u64) -> Self 
+    {
+        let mut sm = SplitMix64::new(state);
+        Xoshiro256StarStar 
+        { 
+            s: [sm.next_u64(), sm.next_u64(), sm.next_u64(), sm.next_u64()] 
+        }
+    }
+}
+
+/// PCG XSL RR 128/64 by O'Neill, the generator usually called `pcg64`
+#[derive(Debug, Clone)]
+pub struct Pcg64
+{
+    state: u128,
+    inc: u128,
+}
+
+impl Pcg64
+{
+    const MULTIPLIER: u128 = 0x2360ED051FC65DA44385DF649FCCF645;
+
+    /// Seeded like `pcg64_srandom_r` of the reference implementation,
+    /// `stream` selecting one of 2^127 independent sequences
+    pub fn new(seed: u128, stream: u128) -> Self
+    {
+        let mut pcg = Pcg64 { state: 0, inc: (stream << 1) | 1 };
+        pcg.step();
+        pcg.state = pcg.state.wrapping_add(seed);
+        pcg.step();
+        pcg
+    }
+
+    fn step(&mut self) -> ()
+    {
+        self.state = self.state.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.inc);
+    }
+}
+
+impl RngCore for Pcg64
+{
+    fn next_u32(&mut self) -> u32 
+    {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 
+    {
+        self.step();
+        let rot = (self.state >> 122) as u32;
+        let xsl = ((self.state >> 64) as u64) ^ (self.state as u64);
+        xsl.rotate_right(rot)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) 
+    {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> 
+    {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg64
+{
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: Self::Seed) -> Self 
+    {
+        let stream = u128::from_le_bytes(seed[16..].try_into().unwrap());
+        let seed = u128::from_le_bytes(seed[..16].try_into().unwrap());
+        Pcg64::new(seed, stream)
+    }
+}
+
+/// Runtime choice of generator, e.g. from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RngKind
+{
+    /// [`rand::rngs::StdRng`], currently ChaCha12
+    #[default]
+    ChaCha,
+    Xoshiro,
+    Pcg,
+    SplitMix,
+}
+
+impl RngKind
+{
+    pub const ALL: [RngKind; 4] = [RngKind::ChaCha, RngKind::Xoshiro, RngKind::Pcg, RngKind::SplitMix];
+
+    /// A generator of this kind seeded from `seed`
+    pub fn seeded(self, seed: u64) -> Box<dyn RngCore + Send>
+    {
+        match self
+        {
+            RngKind::ChaCha => Box::new(rand::rngs::StdRng::seed_from_u64(seed)),
+            RngKind::Xoshiro => Box::new(Xoshiro256StarStar::seed_from_u64(seed)),
+            RngKind::Pcg => Box::new(Pcg64::seed_from_u64(seed)),
+            RngKind::SplitMix => Box::new(SplitMix64::seed_from_u64(seed)),
+        }
+    }
+
+    /// A generator of this kind seeded from the operating system
+    pub fn from_entropy(self) -> Box<dyn RngCore + Send>
+    {
+        self.seeded(rand::random())
+    }
+}
+
+impl Display for RngKind
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self
+        {
+            RngKind::ChaCha => "chacha",
+            RngKind::Xoshiro => "xoshiro",
+            RngKind::Pcg => "pcg",
+            RngKind::SplitMix => "splitmix",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for RngKind
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RngKind::ALL
+        .into_iter()
+        .find(|k| k.to_string() == s.to_ascii_lowercase())
+        .ok_or_else(|| format!("unknown generator '{s}', expected one of chacha, xoshiro, pcg, splitmix"))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use rand::RngCore;
+
+    use super::{Pcg64, RngKind, SplitMix64, Xoshiro256StarStar};
+
+    #[test]
+    fn splitmix_reference_values()
+    {
+        let mut r = SplitMix64::new(1234567);
+        assert_eq!(r.next_u64(), 6457827717110365317);
+        assert_eq!(r.next_u64(), 3203168211198807973);
+        assert_eq!(r.next_u64(), 9817491932198370423);
+    }
+
+    #[test]
+    fn xoshiro_reference_values()
+    {
+        let mut r = Xoshiro256StarStar::new([1, 2, 3, 4]);
+        assert_eq!(r.next_u64(), 11520);
+        assert_eq!(r.next_u64(), 0);
+        assert_eq!(r.next_u64(), 1509978240);
+        assert_eq!(r.next_u64(), 1215971899390074240);
+    }
+
+    #[test]
+    fn pcg_reference_values()
+    {
+        // pcg64_srandom_r(&rng, 42, 54) in the reference implementation
+        let mut r = Pcg64::new(42, 54);
+        assert_eq!(r.next_u64(), 0x86b1da1d72062b68);
+        assert_eq!(r.next_u64(), 0x1304aa46c9853d39);
+        assert_eq!(r.next_u64(), 0xa3670e9e0dd50358);
+    }
+
+    #[test]
+    fn kind_round_trips_through_str()
+    {
+        for k in RngKind::ALL
+        {
+            assert_eq!(k.to_string().parse::<RngKind>(), Ok(k));
+        }
+        assert!("mersenne".parse::<RngKind>().is_err());
+    }
+
+    #[test]
+    fn fill_bytes_handles_partial_words()
+    {
+        let mut a = [0_u8; 13];
+        SplitMix64::new(1).fill_bytes(&mut a);
+
+        let mut r = SplitMix64::new(1);
+        let w0 = r.next_u64().to_le_bytes();
+        let w1 = r.next_u64().to_le_bytes();
+        assert_eq!(a[..8], w0);
+        assert_eq!(a[8..], w1[..5]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Deterministic and quasi-random sources of transform choices.
+//! 
+//! Anything yielding `bool`s can drive a render through
+//! [`MyGrid::fractalize_with_choices`](crate::my_grid::MyGrid::fractalize_with_choices),
+//! including a [`BitStream`](super::bits::BitStream). The sources here
+//! are infinite and noise-free, so the same source always gives the same image.
+
+use std::str::FromStr;
+
+/// A user supplied bit pattern repeated forever, e.g. `"0110"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern
+{
+    bits: Vec<bool>,
+    at: usize,
+}
+
+impl Pattern
+{
+    /// `None` if `bits` is empty
+    pub fn new(bits: Vec<bool>) -> Option<Self>
+    {
+        if bits.is_empty() { return None }
+        Some(Pattern { bits, at: 0 })
+    }
+}
+
+impl FromStr for Pattern
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = 
+            s.chars()
+            .filter(|c| !c.is_whitespace() && *c != '_')
+            .map(
+                |c| 
+                match c
+                {
+                    '0' => Ok(false),
+                    '1' => Ok(true),
+                    _ => Err(format!("pattern may only contain 0 and 1, found '{c}'")),
+                }
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Pattern::new(bits).ok_or_else(|| "pattern is empty".to_string())
+    }
+}
+
+impl Iterator for Pattern
+{
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> 
+    {
+        let b = self.bits[self.at];
+        self.at = (self.at + 1) % self.bits.len();
+        Some(b)
+    }
+}
+
+/// The binary de Bruijn sequence of order `n`, repeated forever.
+/// 
+/// One period has length 2^n and contains every n-bit word exactly once
+/// as a (cyclic) window, so over each period the orbit applies 
+/// every composition of n transforms exactly once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeBruijn(Pattern);
+
+impl DeBruijn
+{
+    pub const MAX_ORDER: usize = 24;
+
+    /// `None` if `order` is zero or above [`DeBruijn::MAX_ORDER`]
+    pub fn new(order: usize) -> Option<Self>
+    {
+        if order == 0 || order > Self::MAX_ORDER { return None }
+
+        // Fredricksen, Kessler and Maiorana: concatenate the Lyndon words 
+        // whose length divides the order, in lexicographic order
+        let mut seq = Vec::with_capacity(1 << order);
+        let mut a = vec![0_u8; order + 1];
+
+        fn db(t: usize, p: usize, n: usize, a: &mut Vec<u8>, seq: &mut Vec<bool>) -> ()
+        {
+            if t > n
+            {
+                if n.is_multiple_of(p)
+                {
+                    seq.extend(a[1..=p].iter().map(|&b| b == 1));
+                }
+            }
+            else
+            {
+                a[t] = a[t - p];
+                db(t + 1, p, n, a, seq);
+                if a[t - p] == 0
+                {
+                    a[t] = 1;
+                    db(t + 1, t, n, a, seq);
+                }
+            }
+        }
+        db(1, 1, order, &mut a, &mut seq);
+
+        Pattern::new(seq).map(DeBruijn)
+    }
+
+    /// Length of one period
+    pub fn period(&self) -> usize
+    {
+        self.0.bits.len()
+    }
+}
+
+impl Iterator for DeBruijn
+{
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> 
+    {
+        self.0.next()
+    }
+}
+
+/// Low-discrepancy choices from the additive recurrence `u_i = frac(offset + i * alpha)`.
+/// 
+/// Step `i` picks `true` when `u_i < p`. With an irrational `alpha` the 
+/// fraction of `true` converges to `p` as fast as possible and the sequence
+/// never repeats; the default golden ratio step gives a Sturmian sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weyl
+{
+    alpha: f64,
+    p: f64,
+    u: f64,
+}
+
+impl Default for Weyl
+{
+    fn default() -> Self 
+    {
+        // 1 / golden ratio
+        Weyl::new(0.618_033_988_749_895, 0.5, 0.0)
+    }
+}
+
+impl Weyl
+{
+    pub fn new(alpha: f64, p: f64, offset: f64) -> Self
+    {
+        Weyl { alpha: alpha.rem_euclid(1.0), p, u: offset.rem_euclid(1.0) }
+    }
+}
+
+impl Iterator for Weyl
+{
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> 
+    {
+        let b = self.u < self.p;
+        self.u += self.alpha;
+        if self.u >= 1.0 { self.u -= 1.0 }
+        Some(b)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::collections::HashSet;
+
+    use super::{DeBruijn, Pattern, Weyl};
+
+    #[test]
+    fn pattern_repeats()
+    {
+        let p: Pattern = "0110".parse().unwrap();
+        let v: Vec<bool> = p.take(9).collect();
+        assert_eq!(v, [false, true, true, false, false, true, true, false, false]);
+
+        assert!("".parse::<Pattern>().is_err());
+        assert!("012".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn de_bruijn_contains_every_window_once()
+    {
+        for n in 1..=10
+        {
+            let d = DeBruijn::new(n).unwrap();
+            let period = d.period();
+            assert_eq!(period, 1 << n);
+
+            // a full period plus n - 1 bits covers every cyclic window
+            let bits: Vec<bool> = d.take(period + n - 1).collect();
+            let windows: HashSet<&[bool]> = bits.windows(n).collect();
+            assert_eq!(windows.len(), period);
+        }
+
+        assert_eq!(DeBruijn::new(0), None);
+    }
+
+    #[test]
+    fn weyl_frequency_matches_p()
+    {
+        let ones = Weyl::new(0.618_033_988_749_895, 0.3, 0.0).take(10_000).filter(|&b| b).count();
+        assert!((2_990..=3_010).contains(&ones));
+    }
+}
@@ -1,4 +1,5 @@
-#[allow(non_snake_case)]
-
+pub mod bench;
+pub mod error;
 pub mod fractal;
 pub mod my_grid;
+pub mod render;
@@ -1,198 +1,1092 @@
-use std::time::Instant;
+use std::{io::IsTerminal, path::{Path, PathBuf}, process::ExitCode, time::{Duration, Instant}};
 
-use serde::{Deserialize, Serialize};
-use RustFractal::{fractal::Fractalize, my_grid::{MyGrid, MyGridPar, MyGreyImage}};
+use clap::{Args, Parser, Subcommand};
+use RustFractal::{
+    bench::{chart, compare::{compare, Significance, Verdict}, history::{self, History, Machine, Run}, log_spaced, Measurement, Report, Sweep}, 
+    error::FractalError,
+    fractal::{markov::{self, TransitionMatrix, Weights}, rng::RngKind}, 
+    my_grid::{dyn_grid::{DynGrid, PixelType}, MyGrid, Viewport}, 
+    render::{
+        checkpoint::Checkpoint, histogram::Histogram, noise::NoiseMap, progress::Progress, random_seed, scene::{Scene, SceneError}, 
+        cancel::CancelToken, convergence::Convergence, dyn_session::DynSession, exposure::Exposure, memory::{parse_bytes, MemoryEstimate}, session::{Outcome, Overflow, RunOptions}, 
+        stats::{Phases, RenderStats}, tone::{Palette, ToneMap}, RenderSettings, Strategy
+    }
+};
 
-fn time_and_save(dim: usize, num_points: usize) -> f64
+/// Render the fractal from the command line
+#[derive(Debug, Parser)]
+#[command(name = "RustFractal", version, about)]
+struct Cli
 {
-    let start = Instant::now();
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command
+{
+    /// Render an image and save it
+    Render(Box<RenderArgs>),
+    /// Tone map a histogram saved by `render --save-histogram` without rendering again
+    Tonemap(TonemapArgs),
+    /// Carry on with a render from a checkpoint written by `render --checkpoint`
+    Resume(ResumeArgs),
+    /// Add up histograms of the same scene rendered separately, e.g. with different seeds
+    Merge(MergeArgs),
+    /// Time renders over a sweep of sizes, point counts, strategies and thread counts
+    Bench(BenchArgs),
+    /// Compare two benchmark runs and flag the cases that got significantly slower
+    Compare(CompareArgs),
+    /// Draw SVG charts of a benchmark run
+    Chart(ChartArgs),
+}
+
+#[derive(Debug, Args)]
+struct CheckpointArgs
+{
+    /// Periodically save the unfinished render here, for the `resume` subcommand.
+    /// Removed once the render completes.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value = "60", value_parser = parse_seconds)]
+    checkpoint_every: Duration,
+}
+
+#[derive(Debug, Args)]
+struct RenderArgs
+{
+    /// Scene file (.toml or .json) to start from. 
+    /// Any other option given overrides the value from the scene.
+    #[arg(long)]
+    scene: Option<PathBuf>,
+
+    /// Write the scene actually rendered, seed included, to this .toml or .json file
+    #[arg(long)]
+    save_scene: Option<PathBuf>,
+
+    /// Also save the raw hit counts, for the `tonemap` subcommand
+    #[arg(long)]
+    save_histogram: Option<PathBuf>,
+
+    /// Also write the render stats here as JSON
+    #[arg(long)]
+    stats: Option<PathBuf>,
+
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+
+    /// Image size, either `N` for a square or `WIDTHxHEIGHT` [default: 1024]
+    #[arg(long, value_parser = parse_size)]
+    size: Option<(usize, usize)>,
+
+    /// Number of points to plot [default: 10000000]
+    #[arg(short = 'n', long)]
+    points: Option<usize>,
+
+    /// Pick the number of points for this many hits per covered pixel, from a short pilot run.
+    /// `mean:<hits>` or `median:<hits>`, a bare number being a mean.
+    #[arg(long, conflicts_with = "points")]
+    exposure: Option<Exposure>,
+
+    /// Stop after this many seconds, keeping the points plotted so far.
+    /// Unless `-n` or the scene sets one, there is then no limit on the points.
+    #[arg(long, value_parser = parse_seconds)]
+    time: Option<Duration>,
 
-    let mut img = 
-        MyGrid::<u8>::new(dim, dim);
+    /// Stop once doubling the points changes the image by less than this relative amount, e.g. 0.01.
+    /// Unless `-n` or the scene sets one, there is then no limit on the points.
+    #[arg(long, value_parser = parse_tolerance)]
+    converge: Option<f64>,
 
-    img.fractalize(num_points);
+    /// Rotation angle of the first map, in radians
+    #[arg(long, allow_negative_numbers = true)]
+    rot: Option<f64>,
 
-    let img: MyGreyImage<_> = img.into();
-    let _ = img.save("mutex_grid_fractal.png");
-    // let _ = img.save("mutex_grid_fractal.bmp");
+    /// Angle added by the polar map, in radians
+    #[arg(long, allow_negative_numbers = true)]
+    theta_offset: Option<f64>,
 
-    let dur = start.elapsed().as_secs_f64();
-    dur
+    /// Relative weights of the polar map and the rotation, e.g. `1,3` [default: 1,1]
+    #[arg(long, value_parser = parse_weights)]
+    weights: Option<Weights>,
+
+    /// Transition matrix picking each transform by the one before, row by row, e.g. `0.9,0.1,0.3,0.7`.
+    /// Index 0 is the polar map and 1 the rotation.
+    #[arg(long, value_parser = parse_markov, conflicts_with = "weights")]
+    markov: Option<TransitionMatrix>,
+
+    /// Region of the plane to draw, `X_MIN,X_MAX,Y_MIN,Y_MAX` [default: -1,1,-1,1]
+    #[arg(long, value_parser = parse_viewport, allow_hyphen_values = true)]
+    viewport: Option<Viewport>,
+
+    /// Seed for the generator. Picked at random and printed when left out.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// serial, ensemble, parallel or exhaustive [default: serial]
+    #[arg(long)]
+    strategy: Option<Strategy>,
+
+    /// chacha, xoshiro, pcg or splitmix [default: chacha]
+    #[arg(long)]
+    rng: Option<RngKind>,
+
+    /// Threads used by the parallel strategy [default: number of cores]
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Counter type of the pixels, u8, u16, u32 or u64. Hits that do not fit are counted as clipped.
+    #[arg(long, default_value_t = PixelType::U32)]
+    pixel_type: PixelType,
+
+    /// What to do with hits that do not fit in their pixel: clip them, widen the pixel type, or fail
+    #[arg(long, default_value_t = Overflow::Clip)]
+    overflow: Overflow,
+
+    /// Refuse to start a render estimated to need more memory than this, e.g. `512M` or `32G`.
+    /// With `--overflow widen` it is checked again before each widening, counting both grids.
+    #[arg(long, default_value = "8G", value_parser = parse_bytes)]
+    memory_budget: u64,
+
+    /// linear, sqrt, log or gamma:<g> [default: log]
+    #[arg(long)]
+    tone: Option<ToneMap>,
+
+    /// grey, fire, ice or viridis [default: grey]
+    #[arg(long)]
+    palette: Option<Palette>,
+
+    /// Where to save the image. The format follows the extension. [default: fractal.png]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Render in independently seeded batches and save the relative error of every pixel
+    /// as an image here, black for none and white for as large as the count itself.
+    /// Hits that do not fit in their pixel are clipped.
+    #[arg(long, conflicts_with_all = ["time", "converge", "checkpoint", "overflow"])]
+    noise_map: Option<PathBuf>,
+
+    /// Batches rendered for `--noise-map`
+    #[arg(long, default_value_t = 8, requires = "noise_map", value_parser = clap::value_parser!(u32).range(2..))]
+    batches: u32,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-struct Row<T>
-where
-    T: std::fmt::Debug
+#[derive(Debug, Args)]
+struct TonemapArgs
 {
-    name: String,
-    elems: Vec<T>,
+    /// Histogram written by `render --save-histogram`
+    histogram: PathBuf,
+
+    /// linear, sqrt, log or gamma:<g> [default: the one it was rendered with]
+    #[arg(long)]
+    tone: Option<ToneMap>,
+
+    /// grey, fire, ice or viridis [default: the one it was rendered with]
+    #[arg(long)]
+    palette: Option<Palette>,
+
+    /// Where to save the image. The format follows the extension. [default: the path it was rendered to]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 }
 
-impl<T> Row<T>
-where
-    T: std::fmt::Debug
+#[derive(Debug, Args)]
+struct ResumeArgs
+{
+    /// Checkpoint to resume from. Keeps being updated while the render runs.
+    checkpoint: PathBuf,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value = "60", value_parser = parse_seconds)]
+    checkpoint_every: Duration,
+
+    /// Also save the raw hit counts, for the `tonemap` subcommand
+    #[arg(long)]
+    save_histogram: Option<PathBuf>,
+
+    /// Also write the render stats here as JSON
+    #[arg(long)]
+    stats: Option<PathBuf>,
+
+    /// Refuse to widen the pixel type past this much estimated memory, e.g. `512M` or `32G`.
+    /// Only used by renders started with `--overflow widen`.
+    #[arg(long, default_value = "8G", value_parser = parse_bytes)]
+    memory_budget: u64,
+}
+
+#[derive(Debug, Args)]
+struct MergeArgs
+{
+    /// Histograms written by `render --save-histogram`
+    #[arg(required = true, num_args = 2..)]
+    histograms: Vec<PathBuf>,
+
+    /// Where to save the merged histogram
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Also tone map the merged counts as the first histogram's scene says and save the image here
+    #[arg(long)]
+    image: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct BenchArgs
+{
+    /// Image sizes, each `N` or `WIDTHxHEIGHT`, e.g. `1024,4096`
+    #[arg(long, value_delimiter = ',', value_parser = parse_size, default_value = "1024")]
+    sizes: Vec<(usize, usize)>,
+
+    /// Point counts, `N` or `START..END` spread over `--steps` log-spaced steps
+    #[arg(short = 'n', long, value_parser = parse_point_range, default_value = "1000000..100000000")]
+    points: (usize, usize),
+
+    /// Steps between the ends of a point range
+    #[arg(long, default_value_t = 4)]
+    steps: usize,
+
+    /// Strategies to time, e.g. `serial,parallel`
+    #[arg(long, value_delimiter = ',', default_value = "serial")]
+    strategies: Vec<Strategy>,
+
+    /// Thread counts for the parallel strategy [default: number of cores]
+    #[arg(short = 'j', long, value_delimiter = ',')]
+    threads: Vec<usize>,
+
+    /// Renders per case
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    reps: u32,
+
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write one line per case here
+    #[arg(long)]
+    csv: Option<PathBuf>,
+
+    /// Write every timing here
+    #[arg(long)]
+    json: Option<PathBuf>,
+
+    /// Draw SVG charts of the results into this directory
+    #[arg(long)]
+    charts: Option<PathBuf>,
+
+    /// Keep the results in the history, under this machine and the current git revision
+    #[arg(long)]
+    record: bool,
+
+    /// Directory of the history
+    #[arg(long, default_value = history::DEFAULT_DIR)]
+    history: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ChartArgs
+{
+    /// Revision recorded by `bench --record` on this machine, or a file written by `bench --json`
+    run: String,
+
+    /// Directory to draw the charts into
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+
+    /// Directory of the history
+    #[arg(long, default_value = history::DEFAULT_DIR)]
+    history: PathBuf,
+
+    /// Machine whose history to look in, as named in the history directory [default: this one]
+    #[arg(long)]
+    machine: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct CompareArgs
+{
+    /// Revision recorded by `bench --record` on this machine, or a file written by `bench --json`
+    base: String,
+
+    /// Same as `base`, for the run to check
+    new: String,
+
+    /// Directory of the history
+    #[arg(long, default_value = history::DEFAULT_DIR)]
+    history: PathBuf,
+
+    /// Machine whose history to look in, as named in the history directory [default: this one]
+    #[arg(long)]
+    machine: Option<String>,
+
+    /// Largest p-value taken as significant
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    /// Smallest relative change of the mean time worth flagging
+    #[arg(long, default_value_t = 0.02)]
+    min_change: f64,
+}
+
+impl RenderArgs
 {
-    fn init(name: String) -> Self
+    /// The scene file, or the defaults, with every option given on the command line applied on top
+    fn scene(&self) -> Result<Scene, SceneError>
     {
-        Self { name, elems: vec![] }
+        let mut scene = match &self.scene
+        {
+            Some(path) => Scene::load(path)?,
+            None =>
+            {
+                let mut scene = Scene::default();
+                scene.render.seed = random_seed();
+                scene.render.threads = default_threads();
+                scene
+            }
+        };
+
+        let r = &mut scene.render;
+        let stops = self.time.is_some() || self.converge.is_some();
+        if stops && self.scene.is_none() { r.points = None }
+        if let Some((width, height)) = self.size { (r.cols, r.rows) = (width, height) }
+        if let Some(points) = self.points { r.points = Some(points) }
+        if let Some(rot) = self.rot { r.transform.rot = rot }
+        if let Some(theta_offset) = self.theta_offset { r.transform.theta_offset = theta_offset }
+        if let Some(weights) = self.weights { r.weights = weights }
+        if let Some(matrix) = self.markov { r.markov = Some(matrix) }
+        if let Some(viewport) = self.viewport { r.viewport = viewport }
+        if let Some(seed) = self.seed { r.seed = seed }
+        if let Some(strategy) = self.strategy { r.strategy = strategy }
+        if let Some(rng) = self.rng { r.rng = rng }
+        if let Some(threads) = self.threads { r.threads = threads }
+
+        let i = &mut scene.image;
+        if let Some(tone) = self.tone { i.tone = tone }
+        if let Some(palette) = self.palette { i.palette = palette }
+        if let Some(output) = &self.output 
+        { 
+            i.path = output.clone();
+            // an explicit path picks its own format
+            i.format = None;
+        }
+
+        if scene.render.points.is_none() && !stops && self.exposure.is_none()
+        {
+            let message = "is unlimited, so the render needs --time or --converge to stop".to_string();
+            return Err(SceneError::Invalid { field: "render.points".to_string(), message });
+        }
+
+        scene.validate()?;
+        Ok(scene)
     }
+}
+
+/// ` of <points>` for a render with a limit on the points, nothing otherwise
+fn of_total(points: Option<usize>) -> String
+{
+    points.map(|p| format!(" of {p}")).unwrap_or_default()
+}
+
+fn parse_size(s: &str) -> Result<(usize, usize), String>
+{
+    let parse = |d: &str| -> Result<usize, String>
+    {
+        match d.trim().parse::<usize>()
+        {
+            Ok(0) => Err("dimensions must be positive".to_string()),
+            Ok(d) => Ok(d),
+            Err(e) => Err(format!("invalid dimension '{d}': {e}")),
+        }
+    };
 
-    fn add_elem(&mut self, elem: T) -> ()
+    match s.split_once(['x', 'X'])
     {
-        self.elems.push(elem);
+        Some((w, h)) => Ok((parse(w)?, parse(h)?)),
+        None => parse(s).map(|d| (d, d)),
     }
 }
 
+/// Comma separated floats, exactly `N` of them
+fn parse_floats<const N: usize>(s: &str) -> Result<[f64; N], String>
+{
+    let v = 
+        s.split(',')
+        .map(|f| f.trim().parse::<f64>().map_err(|e| format!("invalid number '{f}': {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-struct Table<T>
-where
-    T: std::fmt::Debug
+    v.try_into().map_err(|v: Vec<f64>| format!("expected {N} comma separated numbers, found {}", v.len()))
+}
+
+fn parse_weights(s: &str) -> Result<Weights, String>
+{
+    let w = Weights(parse_floats::<2>(s)?);
+    w.validate()?;
+    Ok(w)
+}
+
+fn parse_markov(s: &str) -> Result<TransitionMatrix, String>
 {
-    name: String,
-    col_names: Vec<String>,
-    rows: Vec<Row<T>>,
+    let [a, b, c, d] = parse_floats::<4>(s)?;
+    let m = [[a, b], [c, d]];
+    markov::validate(&m)?;
+    Ok(m)
 }
 
-// impl<T> std::fmt::Display for Table<T>
-// where
-//     T: std::fmt::Debug,
-// {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         write!(f, "{}\n", self.name)?;
-//         for col_name in &self.col_names
-//         {
-//             write!(f, "{}\t", col_name)?;
-//         } writeln!(f)?;
+fn parse_viewport(s: &str) -> Result<Viewport, String>
+{
+    let [x_min, x_max, y_min, y_max] = parse_floats::<4>(s)?;
+    let v = Viewport { x_min, x_max, y_min, y_max };
+    v.validate()?;
+    Ok(v)
+}
 
-//         write!(f, "")
-//     }
-// }
+fn parse_seconds(s: &str) -> Result<Duration, String>
+{
+    let secs = s.trim().trim_end_matches('s').parse::<f64>().map_err(|e| format!("invalid number of seconds '{s}': {e}"))?;
+    Duration::try_from_secs_f64(secs).map_err(|e| format!("invalid number of seconds '{s}': {e}"))
+}
+
+fn parse_point_range(s: &str) -> Result<(usize, usize), String>
+{
+    let (start, end) = s.split_once("..").unwrap_or((s, s));
+    let parse = |n: &str| n.trim().replace('_', "").parse::<usize>().map_err(|e| format!("invalid number of points '{n}': {e}"));
+    let (start, end) = (parse(start)?, parse(end)?);
+
+    if start == 0 || end < start { return Err(format!("invalid point range '{s}'")) }
+    Ok((start, end))
+}
+
+fn parse_tolerance(s: &str) -> Result<f64, String>
+{
+    match s.trim().parse::<f64>()
+    {
+        Ok(t) if t > 0.0 && t.is_finite() => Ok(t),
+        Ok(_) => Err("the tolerance must be positive".to_string()),
+        Err(e) => Err(format!("invalid tolerance '{s}': {e}")),
+    }
+}
 
-fn main() {
+fn default_threads() -> usize
+{
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+}
+
+fn render(args: RenderArgs) -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut scene = args.scene()?;
+
+    println!("seed: {}", scene.render.seed);
+
+    if let Some(exposure) = args.exposure
+    {
+        let start = Instant::now();
+        let points = scene.render.expose(exposure)?;
+        println!("{points} points for {exposure} hits, estimated in {} seconds", start.elapsed().as_secs_f64());
+    }
+
+    if let Some(path) = &args.save_scene
+    {
+        scene.save(path)?;
+    }
+
+    let outputs = Outputs { histogram: args.save_histogram, stats: args.stats };
+    if let Some(path) = &args.noise_map
+    {
+        let estimate = MemoryEstimate::of(&scene, args.pixel_type.size())?.with_noise();
+        println!("memory: {}", estimate.check(args.memory_budget)?);
+        return render_with_noise(scene, args.pixel_type, args.batches as usize, path, &outputs);
+    }
+
+    let mut estimate = MemoryEstimate::of(&scene, args.pixel_type.size())?;
+    if args.converge.is_some() { estimate = estimate.with_convergence() }
+    println!("memory: {}", estimate.check(args.memory_budget)?);
+
+    let start = Instant::now();
+    // checked above, counting convergence too
+    let session = DynSession::try_alloc(scene, args.pixel_type)?;
+    let phases = Phases { grid_creation: start.elapsed().as_secs_f64(), ..Default::default() };
+
+    let options = RunOptions
+    {
+        checkpoint: args.checkpoint.checkpoint,
+        checkpoint_every: args.checkpoint.checkpoint_every,
+        budget: args.time,
+        converge: args.converge.map(|tolerance| Convergence { tolerance, ..Default::default() }),
+        overflow: args.overflow,
+        memory_budget: Some(args.memory_budget),
+        ..Default::default()
+    };
+    complete(session, options, &outputs, phases)
+}
+
+fn resume(args: ResumeArgs) -> Result<(), Box<dyn std::error::Error>>
+{
     let start = Instant::now();
+    let session = DynSession::resume(Checkpoint::load(&args.checkpoint)?)?;
+    let phases = Phases { grid_creation: start.elapsed().as_secs_f64(), ..Default::default() };
+    println!(
+        "resuming at {}{} points, seed: {}", 
+        session.points_done(), of_total(session.points_total()), session.scene().render.seed
+    );
+
+    let options = RunOptions 
+    { 
+        checkpoint: Some(args.checkpoint), 
+        checkpoint_every: args.checkpoint_every, 
+        overflow: session.overflow(), 
+        memory_budget: Some(args.memory_budget),
+        ..Default::default() 
+    };
+    let outputs = Outputs { histogram: args.save_histogram, stats: args.stats };
+    complete(session, options, &outputs, phases)
+}
+
+/// Files written along with the image
+#[derive(Debug, Default)]
+struct Outputs
+{
+    histogram: Option<PathBuf>,
+    stats: Option<PathBuf>,
+}
+
+/// Run the session as `options` says, with a progress bar on a terminal, then save the results.
+/// On SIGINT or SIGTERM save the image so far and a checkpoint to resume from instead.
+fn complete(
+    mut session: DynSession, 
+    options: RunOptions, 
+    outputs: &Outputs,
+    mut phases: Phases
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let checkpoint = options.checkpoint.clone();
+
+    let show_bar = std::io::stderr().is_terminal();
+    let mut options = options;
+    if show_bar
+    {
+        options.observer = Some(Box::new(progress_bar));
+        options.report_every = Duration::from_millis(200);
+    }
+
+    options.cancel = Some(cancel_on_signal()?);
+
+    let start = Instant::now();
+    let widen_from = session.pixel_type();
+    match session.run(&mut options)?
+    {
+        Outcome::Completed => (),
+        Outcome::Cancelled =>
+        {
+            // end the unfinished bar
+            if show_bar { eprintln!() }
+            return save_interrupted(session, checkpoint.as_deref());
+        },
+        Outcome::OutOfTime =>
+        {
+            if show_bar { eprintln!() }
+            println!("out of time after {} points", session.points_done());
+        },
+        Outcome::Converged =>
+        {
+            if show_bar { eprintln!() }
+            println!("converged after {} points", session.points_done());
+        },
+        // only stops a single session, `DynSession` widens and carries on
+        Outcome::Overflowed => unreachable!("the session widens on overflow"),
+    }
+    if session.pixel_type() != widen_from
+    {
+        println!("widened pixels from {widen_from} to {}", session.pixel_type());
+    }
+
+    // the image and histogram supersede it
+    if let Some(path) = checkpoint.filter(|p| p.exists())
+    {
+        std::fs::remove_file(path)?;
+    }
+    phases.fractalize = start.elapsed().as_secs_f64();
 
-    println!("Hello, world!");
+    let mut scene = session.scene().clone();
+    // less than asked for if the render stopped early
+    scene.render.points = Some(session.points_done());
+    save(scene, session.into_grid(), outputs, phases)
+}
 
-    // test();
+/// Render `batches` batches, report the noise and save its map to `path` along with the image.
+/// On SIGINT or SIGTERM save the image so far, and the map of the batches that finished.
+fn render_with_noise(
+    mut scene: Scene, 
+    pixel_type: PixelType,
+    batches: usize, 
+    path: &Path, 
+    outputs: &Outputs
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let cancel = cancel_on_signal()?;
 
-    let mut img = MyGrid::<u8>::new(4096, 4096);
-    println!("time to create grid: {} seconds", start.elapsed().as_secs_f64());
     let start = Instant::now();
-    img.fractalize(1_000_000_000);
-    println!("Time to fractalize: {} seconds", start.elapsed().as_secs_f64());
+    let (grid, points, outcome, noise) = match pixel_type
+    {
+        PixelType::U8 => noise_batches::<u8>(&scene.render, batches, &cancel)?,
+        PixelType::U16 => noise_batches::<u16>(&scene.render, batches, &cancel)?,
+        PixelType::U32 => noise_batches::<u32>(&scene.render, batches, &cancel)?,
+        PixelType::U64 => noise_batches::<u64>(&scene.render, batches, &cancel)?,
+    };
+    // the batches create their own grids
+    let phases = Phases { fractalize: start.elapsed().as_secs_f64(), ..Default::default() };
+
+    match &noise
+    {
+        Some(noise) =>
+        {
+            let median = noise.median_relative_error();
+            println!("median relative error of covered pixels: {:.2}%", median * 100.0);
+            if median > 0.0
+            {
+                println!("about {:.0} points for 1%", noise.points() as f64 * (median / 0.01).powi(2));
+            }
+            noise.colorize(ToneMap::Linear, Palette::Grey).save(path)?;
+        },
+        None => eprintln!("fewer than two batches finished, no noise map saved"),
+    }
+
+    if outcome == Outcome::Cancelled
+    {
+        let total = of_total(scene.render.points);
+        scene.save_image(&grid)?;
+        eprintln!("interrupted after {points}{total} points, saved {}", scene.image.path.display());
+
+        // partial results saved, see main
+        return Err(Box::new(FractalError::Cancelled));
+    }
+
+    scene.render.points = Some(points);
+    save(scene, grid, outputs, phases)
+}
+
+/// [`RenderSettings::render_with_noise`] into pixels of type `P`
+fn noise_batches<P>(
+    render: &RenderSettings, 
+    batches: usize, 
+    cancel: &CancelToken
+) -> Result<(DynGrid, usize, Outcome, Option<NoiseMap>), FractalError>
+where
+    P: image::Primitive + Default + num_traits::CheckedAdd + Send,
+    MyGrid<P>: Into<DynGrid>
+{
+    let (rendered, noise) = render.render_with_noise::<P>(batches, cancel)?;
+    Ok((rendered.grid.into(), rendered.points, rendered.outcome, noise))
+}
+
+/// A token cancelled on SIGINT or SIGTERM, exiting at once on a second one
+fn cancel_on_signal() -> Result<CancelToken, ctrlc::Error>
+{
+    let cancel = CancelToken::new();
+    {
+        let cancel = cancel.clone();
+        ctrlc::set_handler(
+            move ||
+            {
+                // a second signal gives up on saving
+                if cancel.is_cancelled() { std::process::exit(130) }
+                cancel.cancel();
+            }
+        )?;
+    }
+    Ok(cancel)
+}
+
+/// Save the image and whatever else `outputs` asks for, then print the stats
+fn save(scene: Scene, grid: DynGrid, outputs: &Outputs, mut phases: Phases) -> Result<(), Box<dyn std::error::Error>>
+{
     let start = Instant::now();
-    let img: MyGreyImage<u8> = img.into();
-    println!("time to into MyGreyImage: {} seconds", start.elapsed().as_secs_f64());
+    let img = scene.colorize(&grid);
+    phases.conversion = start.elapsed().as_secs_f64();
+
     let start = Instant::now();
-    let _ = img.save("improved_rand.png");
-    println!("time to save png: {} seconds", start.elapsed().as_secs_f64());
-
-    // let mut v = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
-
-    // let slice = v.as_mut_slice();
-    // slice.chunks_mut(5).enumerate().for_each(|a| { println!("i: {}, {:?}", a.0, a.1) });
-    
-
-    // Must contain references (pointers) so the array 
-    // has elements of known size at compile time
-    // let mut a : Vec<&dyn std::fmt::Debug> = vec![];
-    // a.push(&5_i32);
-    // a.push(&1.0_f32);
-    // a.push(&"hello there!");
-    // println!("{:?}", a);
-
-    // let mut img = MutexGridPar::<u8>::new(4096, 4096);
-    // let mut img = MyGrid::<u8>::new(1024, 1024);
-    // // let mut img = MyGridPar::<u8>::new(1024, 1024);
-    // // let mut img: sprs::CsMat<u8> = sprs::CsMatBase::zero((1024, 1024));
-    // // let vec: sprs::CsVec<u8> = sprs::CsVecBase::zero();
-    // img.fractalize(1_000_000);
-    
-    // // let img: MyGrid<_> = img.into();
-    // let img : MyGreyImage<_> = img.into();
-    // let _ = img.save("test_par.png");
-
-    // let mut img = MutexGrid::<u8>::new(4096, 4096);
-    // img.fractalize(1_000_000_000);
-    // img.apply_all_in_parallel(4, 
-    //     |p|
-    //     (*p as f64).sqrt() as u8
-    // );
-
-    // let img: MyGreyImage<_> = img.into();
-    // let _ = img.save("sqrtimg.png");
-
-    // let a = Arc::new(RefCell::new(vec![0; 2]));
-    // let mut aa = a.as_ref().borrow_mut();
-    // let mut aaa = a.as_ref().borrow_mut();
-
-    // aa[0] = 1;
-    // aaa[1] = 2;
-    
-    // println!("{:?}", a);
-    // println!("Elapsed time: {}", start.elapsed().as_secs_f64());
-}
-
-fn test() {
-    let mut t = Table::<f64>::default();
-    
-    const NAME: &str = "TrivialSerial";
-    const DIM: usize = 4096;
-    const START: usize = 1_000_000;
-    const END: usize = 1_000_000_000;
-    const NUM_PTS_TESTS: usize = 10;
-    const NUM_REPS: usize = 5;
-    
-    // t.name = "SerialMethod8192x8192".to_string();
-    t.name = format!("{NAME}_{DIM}x{DIM}_{START}_to_{END}_in_{NUM_PTS_TESTS}_steps_{NUM_REPS}_reps_each");
-    
-    let mut cur_pts = START;
-
-    // Find log-gap between start and end and then divide by the number of tests
-    let mult = ((END as f64).log10() - (START as f64).log10()) / (NUM_PTS_TESTS as f64);
-    
-    println!("log diff: {mult}");
-
-    // The correct number to multiply by each time is 10^mult.
-    let mult = 10_f64.powf(mult);
-
-    println!("mult: {mult}");
-
-    for _ in 0..=NUM_PTS_TESTS
-    {
-        println!("points: {cur_pts}");
-
-        let mut r = Row::<f64>::init(format!("{cur_pts}"));
-
-        for _ in 0..NUM_REPS
+    scene.write_image(&img)?;
+    phases.save = start.elapsed().as_secs_f64();
+
+    let mut stats = RenderStats::of_dyn(&grid, &scene.render);
+    stats.phases = phases;
+    println!("{stats}");
+    println!("saved {}", scene.image.path.display());
+
+    if let Some(path) = &outputs.stats
+    {
+        std::fs::write(path, stats.to_json()?)?;
+        println!("saved stats {}", path.display());
+    }
+
+    if let Some(path) = &outputs.histogram
+    {
+        let start = Instant::now();
+        Histogram::new(scene, grid).save(path)?;
+        println!("time to save histogram {}: {} seconds", path.display(), start.elapsed().as_secs_f64());
+    }
+
+    Ok(())
+}
+
+fn save_interrupted(session: DynSession, checkpoint: Option<&Path>) -> Result<(), Box<dyn std::error::Error>>
+{
+    let path = match checkpoint
+    {
+        Some(p) => p.to_path_buf(),
+        None => 
+        {
+            let mut p = session.scene().image.path.clone().into_os_string();
+            p.push(".checkpoint");
+            PathBuf::from(p)
+        }
+    };
+    session.checkpoint().save(&path)?;
+
+    let scene = session.scene().clone();
+    let (done, total) = (session.points_done(), of_total(session.points_total()));
+    scene.save_image(&session.into_grid())?;
+
+    eprintln!("interrupted after {done}{total} points, saved {}", scene.image.path.display());
+    eprintln!("continue with: RustFractal resume {}", path.display());
+
+    // partial results saved, see main
+    Err(Box::new(FractalError::Cancelled))
+}
+
+/// Redraws a one line bar on stderr, ending the line once the render is done
+fn progress_bar(p: &Progress) -> ()
+{
+    const WIDTH: usize = 30;
+
+    let hms = |d: Duration| -> String
+    {
+        let s = d.as_secs();
+        format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+    };
+    let change = p.change.map(|c| format!("change {:.3}%  ", c * 100.0)).unwrap_or_default();
+
+    // stopped by time or convergence rather than by a point count
+    let Some(total) = p.points_total else
+    {
+        eprint!(
+            "\r{:.1} M points {:7.1} Mpts/s  {}  {change}",
+            p.points_done as f64 / 1e6, p.points_per_sec / 1e6, hms(p.elapsed)
+        );
+        return
+    };
+
+    let filled = (p.fraction() * WIDTH as f64) as usize;
+    let eta = p.eta().map_or("?".to_string(), hms);
+
+    eprint!(
+        "\r[{}{}] {:5.1}% {:7.1} Mpts/s  ETA {eta}  {change}",
+        "#".repeat(filled), "-".repeat(WIDTH - filled), p.fraction() * 100.0, p.points_per_sec / 1e6
+    );
+    if p.points_done >= total
+    {
+        eprintln!();
+    }
+}
+
+fn tonemap(args: TonemapArgs) -> Result<(), Box<dyn std::error::Error>>
+{
+    let Histogram { mut scene, grid } = Histogram::load(&args.histogram)?;
+
+    if let Some(tone) = args.tone { scene.image.tone = tone }
+    if let Some(palette) = args.palette { scene.image.palette = palette }
+    if let Some(output) = args.output 
+    { 
+        scene.image.path = output;
+        scene.image.format = None;
+    }
+
+    scene.save_image(&grid)?;
+    println!("saved {}", scene.image.path.display());
+
+    Ok(())
+}
+
+fn merge(args: MergeArgs) -> Result<(), Box<dyn std::error::Error>>
+{
+    let histograms = 
+        args.histograms.iter()
+        .map(|p| Histogram::load(p).map_err(|e| format!("{}: {e}", p.display())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let merged = Histogram::merge(&histograms)?;
+    merged.save(&args.output)?;
+    println!(
+        "merged {} histograms{} points, into {} as {}", 
+        histograms.len(), merged.scene.render.points.map(|p| format!(", {p}")).unwrap_or_default(), args.output.display(), merged.grid.pixel_type()
+    );
+
+    if let Some(path) = args.image
+    {
+        let mut scene = merged.scene;
+        scene.image.path = path;
+        scene.image.format = None;
+        scene.save_image(&merged.grid)?;
+        println!("saved {}", scene.image.path.display());
+    }
+
+    Ok(())
+}
+
+fn bench(args: BenchArgs) -> Result<(), Box<dyn std::error::Error>>
+{
+    let sweep = Sweep
+    {
+        sizes: args.sizes,
+        points: log_spaced(args.points.0, args.points.1, args.steps),
+        strategies: args.strategies,
+        threads: if args.threads.is_empty() { vec![default_threads()] } else { args.threads },
+        repetitions: args.reps as usize,
+        base: RenderSettings { seed: args.seed, ..Default::default() },
+    };
+
+    println!(
+        "{:>11} {:>12} {:>10} {:>7} {:>10} {:>10} {:>10} {:>10}", 
+        "size", "points", "strategy", "threads", "mean s", "median s", "stddev s", "Mpts/s"
+    );
+    let report = sweep.run(
+        |m: &Measurement|
         {
-            let time = time_and_save(DIM, cur_pts);
-            r.add_elem(time);
+            let (c, s) = (&m.case, &m.summary);
+            println!(
+                "{:>11} {:>12} {:>10} {:>7} {:>10.4} {:>10.4} {:>10.4} {:>10.2}",
+                format!("{}x{}", c.width, c.height), c.points, c.strategy.to_string(), c.threads, 
+                s.mean, s.median, s.stddev, s.points_per_sec / 1e6
+            );
         }
-    
-        t.rows.push(r);
+    )?;
+
+    if let Some(path) = args.csv
+    {
+        report.save_csv(&path)?;
+        println!("saved {}", path.display());
+    }
+    if let Some(path) = args.json
+    {
+        report.save_json(&path)?;
+        println!("saved {}", path.display());
+    }
+    if let Some(dir) = args.charts
+    {
+        save_charts(&report, &dir)?;
+    }
+    if args.record
+    {
+        let run = Run::now(report);
+        let path = History::new(&args.history).record(&run)?;
+        println!("recorded {} as {}", run.revision, path.display());
+    }
+
+    Ok(())
+}
+
+/// Write `time_vs_points.svg`, and `speedup_vs_threads.svg` if there is a speedup to draw, into `dir`
+fn save_charts(report: &Report, dir: &Path) -> Result<(), Box<dyn std::error::Error>>
+{
+    std::fs::create_dir_all(dir)?;
+
+    let mut charts = vec![("time_vs_points.svg", chart::time_vs_points(report))];
+    charts.extend(chart::speedup_vs_threads(report).map(|c| ("speedup_vs_threads.svg", c)));
 
-        cur_pts = ((cur_pts as f64) * mult).ceil() as usize;
+    for (name, chart) in charts
+    {
+        let path = dir.join(name);
+        std::fs::write(&path, chart.to_svg())?;
+        println!("saved {}", path.display());
     }
 
-    println!("{:#?}", t);
-    println!("{:?}", postcard::to_stdvec(&t).unwrap());
+    Ok(())
+}
+
+fn draw_charts(args: ChartArgs) -> Result<(), Box<dyn std::error::Error>>
+{
+    let machine = args.machine.unwrap_or_else(|| Machine::current().key());
+    let run = find_run(&args.run, &History::new(&args.history), &machine)?;
+    save_charts(&run.report, &args.output)
+}
+
+/// A file if there is one at `name`, otherwise a revision in the history
+fn find_run(name: &str, history: &History, machine: &str) -> Result<Run, String>
+{
+    if Path::new(name).is_file() { return Run::load(name) }
+
+    history.load(machine, name).map_err(
+        |_|
+        {
+            let recorded: Vec<String> = history.runs(machine).unwrap_or_default().into_iter().map(|r| r.revision).collect();
+            format!("no file or run of revision '{name}' on {machine}, recorded: {}", recorded.join(", "))
+        }
+    )
+}
+
+fn compare_runs(args: CompareArgs) -> Result<(), Box<dyn std::error::Error>>
+{
+    let history = History::new(&args.history);
+    let machine = args.machine.unwrap_or_else(|| Machine::current().key());
+    let (base, new) = (find_run(&args.base, &history, &machine)?, find_run(&args.new, &history, &machine)?);
+
+    let known = |r: &Run| r.machine.hostname != "unknown";
+    if base.machine != new.machine && known(&base) && known(&new)
+    {
+        println!("warning: comparing runs from {} and {}", base.machine.key(), new.machine.key());
+    }
+
+    let significance = Significance { alpha: args.alpha, min_change: args.min_change };
+    let comparisons = compare(&base.report, &new.report, significance);
+    if comparisons.is_empty()
+    {
+        return Err(format!("{} and {} have no case in common", base.revision, new.revision).into());
+    }
+
+    println!(
+        "{:>11} {:>12} {:>10} {:>7} {:>10} {:>10} {:>8} {:>8}  verdict", 
+        "size", "points", "strategy", "threads", base.revision, new.revision, "change", "p"
+    );
+    for c in &comparisons
+    {
+        let k = &c.case;
+        println!(
+            "{:>11} {:>12} {:>10} {:>7} {:>10.4} {:>10.4} {:>+7.1}% {:>8}  {:?}",
+            format!("{}x{}", k.width, k.height), k.points, k.strategy.to_string(), k.threads,
+            c.base.mean, c.new.mean, c.change * 100.0, c.p_value.map_or("-".to_string(), |p| format!("{p:.4}")), c.verdict
+        );
+    }
+
+    let slower = comparisons.iter().filter(|c| c.verdict == Verdict::Slower).count();
+    if slower > 0
+    {
+        return Err(format!("{slower} of {} cases got slower", comparisons.len()).into());
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode
+{
+    let cli = Cli::parse();
+
+    let result = match cli.command
+    {
+        Command::Render(args) => render(*args),
+        Command::Tonemap(args) => tonemap(args),
+        Command::Resume(args) => resume(args),
+        Command::Merge(args) => merge(args),
+        Command::Bench(args) => bench(args),
+        Command::Compare(args) => compare_runs(args),
+        Command::Chart(args) => draw_charts(args),
+    };
+
+    match result
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        // a render stopped by SIGINT or SIGTERM, already reported along with its partial results
+        Err(e) if matches!(e.downcast_ref(), Some(FractalError::Cancelled)) => ExitCode::from(130),
+        Err(e) =>
+        {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
 #[cfg(test)]
 mod test
 {
-    use RustFractal::{fractal::Fractalize, my_grid::{MyGrid, MyGreyImage}};
+    use clap::Parser;
+    use RustFractal::{error::FractalError, fractal::Fractalize, my_grid::{MyGrid, MyGreyImage}, render::{scene::{Scene, SceneError}, Strategy}};
+
+    use super::{parse_point_range, parse_size, Cli, Command};
+
+    #[test]
+    fn parse_render_args()
+    {
+        let cli = Cli::try_parse_from(
+            ["RustFractal", "render", "--size", "640x480", "-n", "1000", "--strategy", "parallel", "--tone", "gamma:2.2", "-o", "out.png"]
+        ).unwrap();
+
+        let Command::Render(args) = cli.command else { panic!("expected render") };
+        let scene = args.scene().unwrap();
+        assert_eq!((scene.render.cols, scene.render.rows), (640, 480));
+        assert_eq!(scene.render.points, Some(1000));
+        assert_eq!(scene.render.strategy, Strategy::Parallel);
+        assert_eq!(scene.image.path.to_str(), Some("out.png"));
+
+        assert_eq!(parse_size("256"), Ok((256, 256)));
+        assert!(parse_size("0x10").is_err());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--palette", "plaid"]).is_err());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--viewport", "-2,2,-1,1"]).is_ok());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--weights", "1,2,3"]).is_err());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--markov", "0.5,0.6,0.5,0.5"]).is_err());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--markov", "0,1,1,0", "--weights", "1,3"]).is_err());
+
+        let cli = Cli::try_parse_from(["RustFractal", "render", "--markov", "0.9,0.1,0.3,0.7"]).unwrap();
+        let Command::Render(args) = cli.command else { panic!("expected render") };
+        assert_eq!(args.scene().unwrap().render.markov, Some([[0.9, 0.1], [0.3, 0.7]]));
+
+        let cli = Cli::try_parse_from(["RustFractal", "render", "--time", "30s"]).unwrap();
+        let Command::Render(args) = cli.command else { panic!("expected render") };
+        assert_eq!(args.time, Some(std::time::Duration::from_secs(30)));
+
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--noise-map", "noise.png", "--batches", "1"]).is_err());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--batches", "4"]).is_err());
+        assert!(Cli::try_parse_from(["RustFractal", "render", "--noise-map", "noise.png", "--overflow", "widen"]).is_err());
+
+        let cli = Cli::try_parse_from(["RustFractal", "resume", "render.checkpoint"]).unwrap();
+        let Command::Resume(args) = cli.command else { panic!("expected resume") };
+        assert_eq!(args.memory_budget, 8 << 30);
+    }
+
+    #[test]
+    fn renders_without_a_limit_save_their_scene()
+    {
+        let path = std::env::temp_dir().join(format!("rf-unlimited-{}.toml", std::process::id()));
+        let cli = Cli::try_parse_from(["RustFractal", "render", "--time", "0.1", "--seed", "1"]).unwrap();
+        let Command::Render(args) = cli.command else { panic!("expected render") };
+
+        let scene = args.scene().unwrap();
+        assert_eq!(scene.render.points, None);
+        scene.save(&path).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("points = \"unlimited\""), "{text}");
+        assert_eq!(Scene::load(&path).unwrap(), scene);
+
+        // the saved scene needs something else to stop it
+        let scene_arg = path.to_str().unwrap();
+        let cli = Cli::try_parse_from(["RustFractal", "render", "--scene", scene_arg]).unwrap();
+        let Command::Render(args) = cli.command else { panic!("expected render") };
+        assert!(matches!(args.scene(), Err(SceneError::Invalid { field, .. }) if field == "render.points"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_bench_args()
+    {
+        let cli = Cli::try_parse_from(
+            ["RustFractal", "bench", "--sizes", "256,640x480", "-n", "1_000..100_000", "--strategies", "serial,parallel", "-j", "1,4"]
+        ).unwrap();
+
+        let Command::Bench(args) = cli.command else { panic!("expected bench") };
+        assert_eq!(args.sizes, vec![(256, 256), (640, 480)]);
+        assert_eq!(args.points, (1000, 100_000));
+        assert_eq!(args.strategies, vec![Strategy::Serial, Strategy::Parallel]);
+        assert_eq!(args.threads, vec![1, 4]);
+
+        assert_eq!(parse_point_range("5000"), Ok((5000, 5000)));
+        assert!(parse_point_range("10..5").is_err());
+    }
 
     #[test]
-    fn test_basic() -> Result<(), image::ImageError>
+    fn test_basic() -> Result<(), FractalError>
     {
         let mut img = MyGrid::<u8>::new(512, 512);
-        img.fractalize(1_000_000);
+        img.fractalize(1_000_000)?;
         let img: MyGreyImage<_> = img.into();
-        img.save("test/test_basic.png")
+        Ok(img.save("test/test_basic.png")?)
     }
 
     #[test]
@@ -208,13 +1102,13 @@ mod test
     }
 
     #[test]
-    fn sprs_grid_fractalize() -> Result<(), image::ImageError>
+    fn sprs_grid_fractalize() -> Result<(), FractalError>
     {
         let mut s: sprs::CsMat<u8> = sprs::CsMatBase::zero((512, 512));
-        s.fractalize(1_000_000);
+        s.fractalize(1_000_000)?;
 
         let s: MyGrid<u8> = s.into();
         let s: MyGreyImage<u8> = s.into();
-        s.save("test/sprs_grid_fractalize.png")
+        Ok(s.save("test/sprs_grid_fractalize.png")?)
     }
 }
\ No newline at end of file
@@ -0,0 +1,443 @@
+//! Noise-free rendering by enumerating transform words.
+//! 
+//! The attractor `A` satisfies `A = f_0(A) ∪ f_1(A)`, so the images of `A`
+//! under all words of length `d` cover it. The tree is walked depth first,
+//! appending each new map on the inside, `f_w ∘ f_i`, so a child's image is
+//! contained in its parent's. Once a node's image is smaller than a pixel
+//! its whole subtree lands in that pixel and is plotted at once.
+//! 
+//! The size of an image is measured on a finite sample of it, so pruning is 
+//! a heuristic: a subtree whose probes share a pixel can still spill over its edges.
+//! Nor does the sample bound the spill. The rotation keeps distances, but the polar map
+//! stretches them by up to π, so a part of `f_w(A)` the probes missed can lie
+//! further out than the probes suggest. More probes make this rarer.
+
+use rand::Rng;
+
+use crate::fractal::{rng::SplitMix64, Transform};
+
+use super::MyGrid;
+
+/// The orbit holds `probes << ORBIT_SHIFT` points, so nodes this far down 
+/// still find their probes on it
+const ORBIT_SHIFT: usize = 6;
+
+/// Plots between looks at whether a walk of the tree should go on
+const POLL: usize = 1 << 12;
+
+/// Settings for [`MyGrid::fractalize_exhaustive`]
+#[derive(Debug, Clone)]
+pub struct AddressTree
+{
+    /// Length of the longest word. There are 2^depth of them.
+    pub depth: usize,
+    /// Fewest points standing in for the attractor when measuring the size of a node's image
+    pub probes: usize,
+    /// Steps of the orbit the probes are found on, before the first probe
+    pub burn_in: usize,
+}
+
+impl Default for AddressTree
+{
+    fn default() -> Self 
+    {
+        AddressTree { depth: 16, probes: 8, burn_in: 1000 }
+    }
+}
+
+impl AddressTree
+{
+    /// Words of this length pick the parts the tree is split into, 
+    /// see [`MyGrid::fractalize_exhaustive_part`]
+    pub fn split_depth(&self) -> usize
+    {
+        self.depth.min(8)
+    }
+
+    pub fn parts(&self) -> usize
+    {
+        1 << self.split_depth()
+    }
+
+    /// Words of full length, each plotted as one hit
+    pub fn leaves(&self) -> u128
+    {
+        1 << self.depth
+    }
+
+    /// Words of full length under each part
+    pub fn part_leaves(&self) -> u128
+    {
+        1 << (self.depth - self.split_depth())
+    }
+
+    /// Points on (or very near) the attractor, found by following 
+    /// an orbit with a fixed seed so the result is reproducible.
+    fn orbit(&self, transform: &Transform) -> Orbit
+    {
+        let mut rng = SplitMix64::new(0x5EED);
+        let (mut x, mut y) = (0.5, 0.5);
+
+        for _ in 0..self.burn_in
+        {
+            (x, y) = transform.apply(x, y, rng.gen());
+        }
+
+        let len = self.probes.max(1) << ORBIT_SHIFT;
+        let mut orbit = Orbit { points: Vec::with_capacity(len), choices: Vec::with_capacity(len) };
+
+        // the first point has no step of the orbit before it
+        orbit.points.push((x, y));
+        orbit.choices.push(false);
+
+        while orbit.points.len() < len
+        {
+            let s = rng.gen();
+            (x, y) = transform.apply(x, y, s);
+            orbit.points.push((x, y));
+            orbit.choices.push(s);
+        }
+        orbit
+    }
+
+    /// Probes needed by a node whose word is `len` long. A leaf is plotted at its first.
+    fn probes_at(&self, len: usize) -> usize
+    {
+        if len == self.depth { 1 } else { self.probes.max(1) }
+    }
+}
+
+/// How far a walk of one part of the tree has got, counted in words of full length
+struct Walk<'a>
+{
+    /// Leaves done before the walk started, whose subtrees are passed over
+    skip: u128,
+    /// Leaves done, skipped ones included
+    done: u128,
+    plotted: usize,
+    go: &'a dyn Fn() -> bool,
+    stopped: bool,
+}
+
+/// A point in the image of a node, `f_w(points[source])`
+type Probe = (f64, f64, usize);
+
+/// Points of an orbit on the attractor, `points[i] = f_{choices[i]}(points[i - 1])`.
+/// 
+/// So a probe of `w` whose source was reached by `f_s` is also a probe of the child `w s`, 
+/// with the point before as its source, and children pick their probes from 
+/// their parent's without applying any map.
+struct Orbit
+{
+    points: Vec<(f64, f64)>,
+    choices: Vec<bool>,
+}
+
+impl Orbit
+{
+    /// The probes of the empty word
+    fn root(&self) -> Vec<Probe>
+    {
+        self.points.iter().enumerate().map(|(i, &(x, y))| (x, y, i)).collect()
+    }
+
+    /// The probes of `parent` that are also probes of its child `word`, 
+    /// whose last map is the one added to `parent`
+    fn pick(&self, parent: &[Probe], word: &[bool]) -> Vec<Probe>
+    {
+        let s = word[word.len() - 1];
+
+        parent.iter()
+        .filter(|&&(_, _, i)| i > 0 && self.choices[i] == s)
+        .map(|&(x, y, i)| (x, y, i - 1))
+        .collect()
+    }
+}
+
+impl<'a> Walk<'a>
+{
+    fn new(skip: u128, go: &'a dyn Fn() -> bool) -> Self
+    {
+        Walk { skip, done: 0, plotted: 0, go, stopped: false }
+    }
+}
+
+impl<P> MyGrid<P>
+where
+    P: image::Primitive + Default + num_traits::CheckedAdd
+{
+    /// Render by walking every transform word up to `tree.depth`.
+    /// 
+    /// Every word of full length adds one hit, so a subtree cut off at 
+    /// depth `k` adds `2^(depth - k)` hits to its pixel and the image matches
+    /// a random render of the same number of points, minus the noise.
+    /// Returns the number of pixels written, one per leaf or pruned subtree.
+    pub fn fractalize_exhaustive(&mut self, transform: &Transform, tree: &AddressTree) -> usize
+    {
+        let orbit = tree.orbit(transform);
+
+        (0..tree.parts())
+        .map(|part| self.exhaustive_part(transform, tree, &orbit, part, &mut Walk::new(0, &|| true)))
+        .sum()
+    }
+
+    /// Render only the subtree under the word whose bits, most significant first, 
+    /// are those of `part`, one of [`AddressTree::parts`].
+    /// Rendering every part, in any order, gives the same grid as
+    /// [`MyGrid::fractalize_exhaustive`], so a long render can be done in pieces.
+    pub fn fractalize_exhaustive_part(&mut self, transform: &Transform, tree: &AddressTree, part: usize) -> usize
+    {
+        let orbit = tree.orbit(transform);
+        self.exhaustive_part(transform, tree, &orbit, part, &mut Walk::new(0, &|| true))
+    }
+
+    /// [`MyGrid::fractalize_exhaustive_part`] without the first `skip` leaves of the part,
+    /// stopping between leaves once `go` turns false, which is asked every few thousand plots.
+    /// Returns how many leaves of the part are done, the skipped ones included, so calling 
+    /// again with that as `skip` carries on where this left off.
+    /// The part is finished once it returns [`AddressTree::part_leaves`].
+    pub fn fractalize_exhaustive_part_while(
+        &mut self, 
+        transform: &Transform, 
+        tree: &AddressTree, 
+        part: usize, 
+        skip: u128, 
+        go: &dyn Fn() -> bool
+    ) -> u128
+    {
+        let orbit = tree.orbit(transform);
+        let mut walk = Walk::new(skip, go);
+        self.exhaustive_part(transform, tree, &orbit, part, &mut walk);
+        walk.done
+    }
+
+    fn exhaustive_part(&mut self, transform: &Transform, tree: &AddressTree, orbit: &Orbit, part: usize, walk: &mut Walk) -> usize
+    {
+        let k = tree.split_depth();
+        let bits: Vec<bool> = (0..k).map(|j| (part >> (k - 1 - j)) & 1 == 1).collect();
+        let mut word = Vec::with_capacity(tree.depth);
+        let mut image = orbit.root();
+
+        // a whole walk of the tree would stop at a pruned ancestor, 
+        // which then belongs to the first part below it
+        for j in 0..k
+        {
+            if image.len() >= tree.probes_at(j) && self.within_one_pixel(&image)
+            {
+                if bits[j..].iter().all(|&s| !s) && walk.skip == 0
+                {
+                    self.plot_subtree(&image, tree.depth - j, walk);
+                }
+                walk.done = tree.part_leaves();
+                return walk.plotted
+            }
+
+            word.push(bits[j]);
+            image = self.probes_of(transform, orbit, &image, &word, tree.probes_at(word.len()));
+        }
+
+        self.visit(transform, tree, orbit, &mut word, &image, walk);
+        walk.plotted
+    }
+
+    /// Plot the subtree under `word`, whose probes are `image`, 
+    /// passing over the leaves the walk skips and stopping where it stops
+    fn visit(
+        &mut self, 
+        transform: &Transform, 
+        tree: &AddressTree,
+        orbit: &Orbit, 
+        word: &mut Vec<bool>, 
+        image: &[Probe],
+        walk: &mut Walk
+    ) -> ()
+    {
+        let remaining = tree.depth - word.len();
+        if walk.stopped { return }
+        if walk.done + (1 << remaining) <= walk.skip
+        {
+            walk.done += 1 << remaining;
+            return
+        }
+
+        // fewer probes than asked for means they already spread over more than a pixel
+        if remaining == 0 || (image.len() >= tree.probes_at(word.len()) && self.within_one_pixel(image))
+        {
+            self.plot_subtree(image, remaining, walk);
+            return
+        }
+
+        for s in [false, true]
+        {
+            word.push(s);
+            let child = self.probes_of(transform, orbit, image, word, tree.probes_at(word.len()));
+            self.visit(transform, tree, orbit, word, &child, walk);
+            word.pop();
+        }
+    }
+
+    /// Plot all `2^remaining` leaves under a node whose probes are `image` at its first,
+    /// unless the walk is told to stop first
+    fn plot_subtree(&mut self, image: &[Probe], remaining: usize, walk: &mut Walk) -> ()
+    {
+        if walk.plotted > 0 && walk.plotted.is_multiple_of(POLL) && !(walk.go)()
+        {
+            walk.stopped = true;
+            return
+        }
+
+        let hits = 1_u64.checked_shl(remaining as u32).unwrap_or(u64::MAX);
+        self.plot_weighted(image[0].0, image[0].1, hits);
+        walk.done += 1 << remaining;
+        walk.plotted += 1;
+    }
+
+    /// The probes of `word`, picked from those of `parent`. While fewer than `min` are 
+    /// picked and they still fit in a pixel, points of the orbit are mapped through `word`, 
+    /// from the last back, so only a node that might be pruned pays for the whole word.
+    fn probes_of(&self, transform: &Transform, orbit: &Orbit, parent: &[Probe], word: &[bool], min: usize) -> Vec<Probe>
+    {
+        let mut image = orbit.pick(parent, word);
+
+        for i in (0..orbit.points.len()).rev()
+        {
+            if image.len() >= min || !self.within_one_pixel(&image) { break }
+
+            // innermost map first
+            let (x, y) = word.iter().rev().fold(orbit.points[i], |(x, y), &s| transform.apply(x, y, s));
+            image.push((x, y, i));
+        }
+        image
+    }
+
+    fn within_one_pixel(&self, points: &[Probe]) -> bool
+    {
+        let (mut x_min, mut x_max) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut y_min, mut y_max) = (f64::INFINITY, f64::NEG_INFINITY);
+
+        for &(x, y, _) in points
+        {
+            (x_min, x_max) = (x_min.min(x), x_max.max(x));
+            (y_min, y_max) = (y_min.min(y), y_max.max(y));
+        }
+
+        (x_max - x_min) * (self.cols as f64) < self.viewport.width()
+        && (y_max - y_min) * (self.rows as f64) < self.viewport.height()
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::cell::Cell;
+
+    use super::{AddressTree, Walk, POLL};
+    use crate::{fractal::Transform, my_grid::MyGrid};
+
+    #[test]
+    fn exhaustive_is_reproducible()
+    {
+        let tree = AddressTree { depth: 12, ..Default::default() };
+
+        let mut a = MyGrid::<u32>::new(64, 64);
+        let mut b = MyGrid::<u32>::new(64, 64);
+        a.fractalize_exhaustive(&Transform::default(), &tree);
+        b.fractalize_exhaustive(&Transform::default(), &tree);
+
+        assert_eq!(a.grid, b.grid);
+        assert!(a.grid.iter().any(|&p| p > 0));
+    }
+
+    #[test]
+    fn coarse_grid_prunes_and_keeps_weight()
+    {
+        let tree = AddressTree { depth: 14, ..Default::default() };
+
+        let mut g = MyGrid::<u64>::new(4, 4);
+        let plotted = g.fractalize_exhaustive(&Transform::default(), &tree);
+
+        assert!(plotted < 1 << tree.depth);
+
+        // every word is accounted for, apart from images leaving the square
+        let total: u64 = g.grid.iter().sum();
+        assert!(total <= 1 << tree.depth);
+        assert!(total > (1 << tree.depth) / 2);
+    }
+
+    #[test]
+    fn picked_probes_are_images_of_their_sources()
+    {
+        let t = Transform::default();
+        let tree = AddressTree { probes: 2, ..Default::default() };
+        let orbit = tree.orbit(&t);
+
+        // everything fits in a single pixel, so every node tops its probes up
+        let g = MyGrid::<u32>::new(1, 1);
+        let (mut word, mut image) = (vec![], orbit.root());
+        for s in [true, false, false, true, true, false, true, false, false, false, true, true, false]
+        {
+            word.push(s);
+            image = g.probes_of(&t, &orbit, &image, &word, tree.probes);
+            assert!(image.len() >= tree.probes);
+
+            for &(x, y, i) in &image
+            {
+                let (u, v) = word.iter().rev().fold(orbit.points[i], |(x, y), &s| t.apply(x, y, s));
+                assert!((x - u).abs() < 1e-9 && (y - v).abs() < 1e-9, "{word:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn parts_add_up_to_the_whole()
+    {
+        let t = Transform::default();
+
+        for (depth, size) in [(3, 64), (12, 64), (12, 4), (12, 2)]
+        {
+            let tree = AddressTree { depth, ..Default::default() };
+
+            // one walk from the root
+            let orbit = tree.orbit(&t);
+            let (mut word, mut walk) = (vec![], Walk::new(0, &|| true));
+            let mut whole = MyGrid::<u32>::new(size, size);
+            whole.visit(&t, &tree, &orbit, &mut word, &orbit.root(), &mut walk);
+            let n = walk.plotted;
+            assert_eq!(walk.done, 1 << depth);
+
+            let mut parts = MyGrid::<u32>::new(size, size);
+            let m: usize = 
+                (0..tree.parts()).rev()
+                .map(|part| parts.fractalize_exhaustive_part(&t, &tree, part))
+                .sum();
+
+            assert_eq!((m, &parts.grid), (n, &whole.grid), "depth {depth} on {size}x{size}");
+        }
+    }
+
+    #[test]
+    fn stopped_part_carries_on_where_it_left_off()
+    {
+        let t = Transform::default();
+        let tree = AddressTree { depth: 21, ..Default::default() };
+        let part = 77;
+
+        let mut whole = MyGrid::<u32>::new(64, 64);
+        whole.fractalize_exhaustive_part(&t, &tree, part);
+
+        // told to stop at every look, so each call plots one batch
+        let mut pieces = MyGrid::<u32>::new(64, 64);
+        let (mut done, mut calls) = (0, 0);
+        while done < tree.part_leaves()
+        {
+            let looks = Cell::new(0);
+            let next = pieces.fractalize_exhaustive_part_while(&t, &tree, part, done, &|| { looks.set(looks.get() + 1); false });
+            assert!(next > done && looks.get() <= 1);
+            (done, calls) = (next, calls + 1);
+        }
+
+        assert_eq!(done, tree.part_leaves());
+        assert!(calls > 1 && calls <= tree.part_leaves().div_ceil(POLL as u128) as usize, "{calls}");
+        assert_eq!(pieces.grid, whole.grid);
+    }
+}
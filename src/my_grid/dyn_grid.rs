@@ -0,0 +1,236 @@
+use std::{fmt::Display, str::FromStr};
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use super::MyGrid;
+
+/// Accumulator types a grid can be stored with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelType
+{
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl PixelType
+{
+    pub const ALL: [PixelType; 4] = [PixelType::U8, PixelType::U16, PixelType::U32, PixelType::U64];
+
+    /// Bytes per pixel
+    pub fn size(&self) -> usize
+    {
+        match self
+        {
+            PixelType::U8 => 1,
+            PixelType::U16 => 2,
+            PixelType::U32 => 4,
+            PixelType::U64 => 8,
+        }
+    }
+
+    /// The next wider type, `None` for `u64`
+    pub fn wider(&self) -> Option<PixelType>
+    {
+        match self
+        {
+            PixelType::U8 => Some(PixelType::U16),
+            PixelType::U16 => Some(PixelType::U32),
+            PixelType::U32 => Some(PixelType::U64),
+            PixelType::U64 => None,
+        }
+    }
+
+    /// The largest count a pixel of this type holds
+    pub fn max_count(&self) -> u64
+    {
+        match self
+        {
+            PixelType::U8 => u8::MAX.into(),
+            PixelType::U16 => u16::MAX.into(),
+            PixelType::U32 => u32::MAX.into(),
+            PixelType::U64 => u64::MAX,
+        }
+    }
+
+    /// This type, or the narrowest wider one, that holds `count` 
+    pub fn widened_for(self, count: u64) -> PixelType
+    {
+        let mut t = self;
+        while count > t.max_count()
+        {
+            match t.wider()
+            {
+                Some(w) => t = w,
+                None => break,
+            }
+        }
+        t
+    }
+}
+
+impl Display for PixelType
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self
+        {
+            PixelType::U8 => "u8",
+            PixelType::U16 => "u16",
+            PixelType::U32 => "u32",
+            PixelType::U64 => "u64",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for PixelType
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PixelType::ALL
+        .into_iter()
+        .find(|k| k.to_string() == s.to_ascii_lowercase())
+        .ok_or_else(|| format!("unknown pixel type '{s}', expected one of u8, u16, u32, u64"))
+    }
+}
+
+/// A grid whose pixel type is only known at runtime, e.g. one read back from disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DynGrid
+{
+    U8(MyGrid<u8>),
+    U16(MyGrid<u16>),
+    U32(MyGrid<u32>),
+    U64(MyGrid<u64>),
+}
+
+/// Run `$body` with `$g` bound to the grid inside `$dyn`, whatever its pixel type
+macro_rules! with_grid {
+    ($dyn:expr, $g:ident => $body:expr) => {
+        match $dyn
+        {
+            $crate::my_grid::dyn_grid::DynGrid::U8($g) => $body,
+            $crate::my_grid::dyn_grid::DynGrid::U16($g) => $body,
+            $crate::my_grid::dyn_grid::DynGrid::U32($g) => $body,
+            $crate::my_grid::dyn_grid::DynGrid::U64($g) => $body,
+        }
+    };
+}
+pub(crate) use with_grid;
+
+impl DynGrid
+{
+    pub fn pixel_type(&self) -> PixelType
+    {
+        match self
+        {
+            DynGrid::U8(_) => PixelType::U8,
+            DynGrid::U16(_) => PixelType::U16,
+            DynGrid::U32(_) => PixelType::U32,
+            DynGrid::U64(_) => PixelType::U64,
+        }
+    }
+
+    pub fn rows(&self) -> usize
+    {
+        with_grid!(self, g => g.rows())
+    }
+
+    pub fn cols(&self) -> usize
+    {
+        with_grid!(self, g => g.cols())
+    }
+
+    pub fn viewport(&self) -> &super::Viewport
+    {
+        with_grid!(self, g => g.viewport())
+    }
+
+    /// A grid of `pixel_type` holding `counts`, given in row-major order.
+    /// Counts too large for the type saturate, the excess counting as clipped.
+    /// Panics if there are not `rows * cols` of them.
+    pub fn from_counts(rows: usize, cols: usize, viewport: super::Viewport, pixel_type: PixelType, counts: &[u64]) -> DynGrid
+    {
+        assert_eq!(counts.len(), rows * cols, "wrong number of counts");
+
+        fn narrow<P>(rows: usize, cols: usize, viewport: super::Viewport, counts: &[u64]) -> MyGrid<P>
+        where
+            P: num_traits::Bounded + TryFrom<u64> + Into<u64>
+        {
+            let max: u64 = P::max_value().into();
+            let clipped = counts.iter().fold(0_u64, |n, &c| n.saturating_add(c.saturating_sub(max)));
+            let grid = counts.iter().map(|&c| P::try_from(c).unwrap_or(P::max_value())).collect();
+            MyGrid { rows, cols, viewport, grid, clipped, carry: None }
+        }
+
+        match pixel_type
+        {
+            PixelType::U8 => DynGrid::U8(narrow(rows, cols, viewport, counts)),
+            PixelType::U16 => DynGrid::U16(narrow(rows, cols, viewport, counts)),
+            PixelType::U32 => DynGrid::U32(narrow(rows, cols, viewport, counts)),
+            PixelType::U64 => DynGrid::U64(narrow(rows, cols, viewport, counts)),
+        }
+    }
+
+    /// See [`MyGrid::clipped`]
+    pub fn clipped(&self) -> u64
+    {
+        with_grid!(self, g => g.clipped())
+    }
+
+    pub(crate) fn add_clipped(&mut self, hits: u64) -> ()
+    {
+        with_grid!(self, g => g.add_clipped(hits))
+    }
+
+    /// The grid in the next wider pixel type, with any carried hits added back, see [`MyGrid::widen`].
+    /// A `u64` grid stays as it is.
+    pub fn widened(self) -> DynGrid
+    {
+        match self
+        {
+            DynGrid::U8(g) => DynGrid::U16(g.widen()),
+            DynGrid::U16(g) => DynGrid::U32(g.widen()),
+            DynGrid::U32(g) => DynGrid::U64(g.widen()),
+            DynGrid::U64(g) => DynGrid::U64(g),
+        }
+    }
+
+    /// Counts widened to `u64`, in row-major order
+    pub fn counts(&self) -> Vec<u64>
+    {
+        with_grid!(self, g => g.as_slice().iter().map(|p| p.to_u64().unwrap_or(u64::MAX)).collect())
+    }
+}
+
+macro_rules! impl_from_grid {
+    ($($p:ty => $variant:ident),*) => {
+        $(
+            impl From<MyGrid<$p>> for DynGrid
+            {
+                fn from(value: MyGrid<$p>) -> Self {
+                    DynGrid::$variant(value)
+                }
+            }
+
+            /// Hands the grid back unchanged if it holds another pixel type
+            impl TryFrom<DynGrid> for MyGrid<$p>
+            {
+                type Error = DynGrid;
+
+                fn try_from(value: DynGrid) -> Result<Self, Self::Error> {
+                    match value
+                    {
+                        DynGrid::$variant(g) => Ok(g),
+                        other => Err(other),
+                    }
+                }
+            }
+        )*
+    };
+}
+impl_from_grid!(u8 => U8, u16 => U16, u32 => U32, u64 => U64);
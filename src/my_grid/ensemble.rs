@@ -0,0 +1,255 @@
+use rand::prelude::*;
+
+use crate::fractal::{markov::ChoiceSource, Transform};
+
+use super::{MyGrid, Viewport};
+
+/// Several independent orbits feeding the same grid.
+/// 
+/// A single long orbit is highly correlated, so short renders show
+/// streaks wherever the orbit happened to linger. Running many walkers
+/// from scattered starting points and interleaving their steps spreads
+/// the same number of points over the attractor much more evenly.
+#[derive(Debug, Clone)]
+pub struct Ensemble
+{
+    /// Number of walkers. Zero is treated as one.
+    pub walkers: usize,
+    /// Steps each walker takes before it starts plotting
+    pub burn_in: usize,
+    /// Where the walkers start
+    pub start: StartRegion,
+}
+
+impl Default for Ensemble
+{
+    fn default() -> Self 
+    {
+        Ensemble 
+        { 
+            walkers: 8, 
+            burn_in: 64, 
+            start: StartRegion::default() 
+        }
+    }
+}
+
+/// Distribution the initial point of each walker is drawn from
+#[derive(Debug, Clone)]
+pub enum StartRegion
+{
+    /// Uniform over `[x_min, x_max] x [y_min, y_max]`
+    Uniform { x_min: f64, x_max: f64, y_min: f64, y_max: f64 },
+    /// Proportional to the hit counts of an earlier render, 
+    /// usually a cheap low resolution one. 
+    /// Build it with [`StartRegion::from_grid`].
+    Density { rows: usize, cols: usize, viewport: Viewport, cdf: Vec<f64> },
+}
+
+impl Default for StartRegion
+{
+    fn default() -> Self 
+    {
+        StartRegion::Uniform { x_min: -1.0, x_max: 1.0, y_min: -1.0, y_max: 1.0 }
+    }
+}
+
+impl StartRegion
+{
+    pub fn from_grid<P>(grid: &MyGrid<P>) -> Self
+    where
+        P: image::Primitive
+    {
+        let mut total = 0.0;
+        let cdf = 
+            grid.grid
+            .iter()
+            .map(
+                |p|
+                {
+                    total += p.to_f64().unwrap_or(0.0);
+                    total
+                }
+            )
+            .collect();
+
+        StartRegion::Density { rows: grid.rows, cols: grid.cols, viewport: grid.viewport, cdf }
+    }
+
+    pub fn sample<R>(&self, rng: &mut R) -> (f64, f64)
+    where
+        R: Rng + ?Sized
+    {
+        match self
+        {
+            StartRegion::Uniform { x_min, x_max, y_min, y_max } =>
+            {
+                (
+                    x_min + (x_max - x_min) * rng.gen::<f64>(),
+                    y_min + (y_max - y_min) * rng.gen::<f64>()
+                )
+            },
+            StartRegion::Density { rows, cols, viewport, cdf } =>
+            {
+                // no pixels to pick from, so anywhere in view will do
+                if rows * cols == 0 || cdf.len() != rows * cols
+                {
+                    let Viewport { x_min, x_max, y_min, y_max } = *viewport;
+                    return StartRegion::Uniform { x_min, x_max, y_min, y_max }.sample(rng)
+                }
+
+                let total = cdf.last().copied().unwrap_or(0.0);
+
+                // an empty render carries no information, pick any pixel
+                let index = 
+                if total > 0.0
+                {
+                    let u = rng.gen::<f64>() * total;
+                    cdf.partition_point(|&c| c <= u).min(cdf.len() - 1)
+                }
+                else
+                {
+                    rng.gen_range(0..(rows * cols))
+                };
+
+                // jittered within the pixel
+                let (r, c) = (index / cols, index % cols);
+                viewport.from_pixel(
+                    r as f64 + rng.gen::<f64>(), 
+                    c as f64 + rng.gen::<f64>(), 
+                    *rows, 
+                    *cols
+                )
+            },
+        }
+    }
+}
+
+impl Ensemble
+{
+    /// Starting points of the walkers drawn from `rng`, each already 
+    /// through its burn-in with choices drawn from `rng` according to `source`
+    pub fn start_walkers<R>(&self, transform: &Transform, source: &ChoiceSource, rng: &mut R) -> Vec<(f64, f64)>
+    where
+        R: Rng + ?Sized
+    {
+        let mut walkers: Vec<(f64, f64)> = 
+            (0..self.walkers.max(1))
+            .map(|_| self.start.sample(rng))
+            .collect();
+
+        for (x, y) in walkers.iter_mut()
+        {
+            for s in source.choices(&mut *rng, self.burn_in)
+            {
+                (*x, *y) = transform.apply(*x, *y, s);
+            }
+        }
+
+        walkers
+    }
+}
+
+impl<P> MyGrid<P>
+where
+    P: image::Primitive + Default + num_traits::CheckedAdd
+{
+    /// Plot `num_points` points shared round-robin between the walkers of `ensemble`.
+    /// The starting points come from `rng`, which then supplies the 
+    /// transform choices, burn-in included, drawn according to `source`.
+    pub fn fractalize_ensemble<R>(
+        &mut self, 
+        num_points: usize, 
+        transform: &Transform, 
+        source: &ChoiceSource,
+        ensemble: &Ensemble, 
+        rng: &mut R
+    ) -> ()
+    where
+        R: Rng + ?Sized
+    {
+        let mut walkers = ensemble.start_walkers(transform, source, rng);
+        self.walk_interleaved(transform, &mut walkers, 0, num_points, source.choices(rng, num_points));
+    }
+
+    /// Step `walkers` round-robin, starting with walker `next`, for up to `num_points` 
+    /// steps in total, plotting every step. 
+    /// Returns the walker due next so a later call can pick up again.
+    pub fn walk_interleaved<I>(
+        &mut self, 
+        transform: &Transform, 
+        walkers: &mut [(f64, f64)], 
+        next: usize, 
+        num_points: usize, 
+        choices: I
+    ) -> usize
+    where
+        I: IntoIterator<Item = bool>
+    {
+        let n = walkers.len();
+        let mut i = next % n;
+
+        for s in choices.into_iter().take(num_points)
+        {
+            let (x, y) = &mut walkers[i];
+            (*x, *y) = transform.apply(*x, *y, s);
+            self.plot(*x, *y);
+
+            i = (i + 1) % n;
+        }
+
+        i
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use rand::SeedableRng;
+
+    use super::{Ensemble, StartRegion};
+    use crate::{fractal::{markov::ChoiceSource, Transform}, my_grid::MyGrid};
+
+    #[test]
+    fn density_samples_stay_in_covered_pixels()
+    {
+        let mut g = MyGrid::<u8>::new(8, 8);
+        g.grid[3 * 8 + 5] = 10;
+
+        let start = StartRegion::from_grid(&g);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        for _ in 0..100
+        {
+            let (x, y) = start.sample(&mut rng);
+            let r = ((y / 2.0 + 0.5) * 8.0) as usize;
+            let c = ((x / 2.0 + 0.5) * 8.0) as usize;
+            assert_eq!((r, c), (3, 5));
+        }
+    }
+
+    #[test]
+    fn density_of_an_empty_grid_falls_back_to_uniform()
+    {
+        let start = StartRegion::from_grid(&MyGrid::<u8>::new(0, 0));
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        for _ in 0..100
+        {
+            let (x, y) = start.sample(&mut rng);
+            assert!((-1.0..=1.0).contains(&x) && (-1.0..=1.0).contains(&y));
+        }
+    }
+
+    #[test]
+    fn ensemble_plots_at_most_num_points()
+    {
+        let mut g = MyGrid::<u32>::new(64, 64);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        g.fractalize_ensemble(10_000, &Transform::default(), &ChoiceSource::default(), &Ensemble::default(), &mut rng);
+
+        let total: u32 = g.grid.iter().sum();
+        assert!(total > 0);
+        assert!(total <= 10_000);
+    }
+}
@@ -1,15 +1,236 @@
+pub mod address;
+pub mod dyn_grid;
+pub mod ensemble;
 pub mod sprs_grid;
 
-use std::{f64::consts::PI, ops::{Deref, DerefMut}, thread};
+use std::{collections::HashMap, f64::consts::PI, ops::{Deref, DerefMut}, thread};
 
-use image::flat;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    error::FractalError,
+    fractal::{bits::BitStream, markov::ChoiceSource, rng::{RngKind, SplitMix64}, Index2D, Index2DError, IndexMut2D, Transform}
+};
+
+/// The rectangle of the plane covered by a grid.
+/// Row 0 is at `y_min` and column 0 at `x_min`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Viewport
+{
+    pub x_min: f64,
+    pub x_max: f64,
+    pub y_min: f64,
+    pub y_max: f64,
+}
+
+impl Default for Viewport
+{
+    fn default() -> Self 
+    {
+        Viewport { x_min: -1.0, x_max: 1.0, y_min: -1.0, y_max: 1.0 }
+    }
+}
+
+impl Viewport
+{
+    /// Fails unless all bounds are finite and each minimum is below its maximum
+    pub fn validate(&self) -> Result<(), String>
+    {
+        let bounds = [self.x_min, self.x_max, self.y_min, self.y_max];
+        if bounds.iter().any(|b| !b.is_finite())
+        {
+            return Err(format!("bounds must be finite, found {bounds:?}"));
+        }
+        if self.x_min >= self.x_max || self.y_min >= self.y_max
+        {
+            return Err("x_min must be below x_max and y_min below y_max".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> f64
+    {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f64
+    {
+        self.y_max - self.y_min
+    }
+
+    /// Fractional `(row, col)` of `(x, y)` on a `rows x cols` grid
+    pub fn to_pixel(&self, x: f64, y: f64, rows: usize, cols: usize) -> (f64, f64)
+    {
+        (
+            (y - self.y_min) / self.height() * rows as f64,
+            (x - self.x_min) / self.width() * cols as f64
+        )
+    }
+
+    /// Inverse of [`Viewport::to_pixel`]
+    pub fn from_pixel(&self, r: f64, c: f64, rows: usize, cols: usize) -> (f64, f64)
+    {
+        (
+            self.x_min + c / cols as f64 * self.width(),
+            self.y_min + r / rows as f64 * self.height()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "GridParts<P>", bound(deserialize = "P: Deserialize<'de>"))]
 pub struct MyGrid<P>
 {
     rows: usize,
     cols: usize,
-    grid: Vec<P>
+    viewport: Viewport,
+    grid: Vec<P>,
+    /// Hits that did not fit in their pixel
+    clipped: u64,
+    /// The hits that did not fit by pixel index, kept only while carrying
+    carry: Option<HashMap<usize, u64>>,
+}
+
+/// Why [`MyGrid::try_new`] could not make a grid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllocError
+{
+    /// More pixels than can be addressed
+    TooLarge { rows: usize, cols: usize },
+    /// The allocator turned the request down
+    OutOfMemory { bytes: usize },
+}
+
+impl std::fmt::Display for AllocError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            AllocError::TooLarge { rows, cols } => write!(f, "a {rows}x{cols} grid has more pixels than can be addressed"),
+            AllocError::OutOfMemory { bytes } => write!(f, "could not allocate {bytes} bytes for the grid"),
+        }
+    }
+}
+
+impl std::error::Error for AllocError {}
+
+/// What a [`MyGrid`] deserializes through, so a mismatched length is caught on load
+#[derive(Deserialize)]
+struct GridParts<P>
+{
+    rows: usize,
+    cols: usize,
+    viewport: Viewport,
+    grid: Vec<P>,
+    clipped: u64,
+    carry: Option<HashMap<usize, u64>>,
+}
+
+impl<P> TryFrom<GridParts<P>> for MyGrid<P>
+{
+    type Error = String;
+
+    fn try_from(value: GridParts<P>) -> Result<Self, Self::Error> {
+        let GridParts { rows, cols, viewport, grid, clipped, carry } = value;
+
+        if rows.checked_mul(cols) != Some(grid.len())
+        {
+            return Err(format!("a {rows}x{cols} grid cannot hold {} pixels", grid.len()));
+        }
+        viewport.validate()?;
+        if let Some(i) = carry.iter().flat_map(|c| c.keys()).find(|&&i| i >= grid.len())
+        {
+            return Err(format!("hits are carried for pixel {i} of {}", grid.len()));
+        }
+
+        Ok(MyGrid { rows, cols, viewport, grid, clipped, carry })
+    }
+}
+
+impl<P> MyGrid<P>
+{
+    pub fn rows(&self) -> usize
+    {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize
+    {
+        self.cols
+    }
+
+    pub fn viewport(&self) -> &Viewport
+    {
+        &self.viewport
+    }
+
+    /// Cover `viewport` instead of the default `[-1, 1) x [-1, 1)`.
+    /// Counts already in the grid are kept as they are.
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self
+    {
+        self.viewport = viewport;
+        self
+    }
+
+    /// Hit counts in row-major order
+    pub fn as_slice(&self) -> &[P]
+    {
+        &self.grid
+    }
+
+    /// Hits that did not fit because their pixel was at the largest count `P` holds,
+    /// whether they were dropped or carried
+    pub fn clipped(&self) -> u64
+    {
+        self.clipped
+    }
+
+    /// From now on keep the hits that do not fit, by pixel, instead of only counting them,
+    /// so that [`MyGrid::widen`] can add them back
+    pub fn start_carrying(&mut self) -> ()
+    {
+        self.carry.get_or_insert_with(HashMap::new);
+    }
+
+    pub fn is_carrying(&self) -> bool
+    {
+        self.carry.is_some()
+    }
+
+    /// Whether hits are waiting in the carry for a wider grid
+    pub fn has_carry(&self) -> bool
+    {
+        self.carry.as_ref().is_some_and(|c| !c.is_empty())
+    }
+
+    /// Count `hits` that did not fit in pixels of a grid this one was made from
+    pub(crate) fn add_clipped(&mut self, hits: u64) -> ()
+    {
+        self.clipped = self.clipped.saturating_add(hits);
+    }
+
+    /// Record `hits` that did not fit in pixel `i`
+    fn clip(&mut self, i: usize, hits: u64) -> ()
+    {
+        self.clipped = self.clipped.saturating_add(hits);
+        if let Some(carry) = &mut self.carry
+        {
+            let c = carry.entry(i).or_default();
+            *c = c.saturating_add(hits);
+        }
+    }
+
+    /// Index into the grid of the pixel under `(x, y)`, if inside the viewport
+    fn pixel_index(&self, x: f64, y: f64) -> Option<usize>
+    {
+        let (r, c) = self.viewport.to_pixel(x, y, self.rows, self.cols);
+
+        if !(0.0..self.rows as f64).contains(&r) || !(0.0..self.cols as f64).contains(&c) { return None }
+
+        Some(r as usize * self.cols + c as usize)
+    }
 }
 
 impl<P> MyGrid<P>
@@ -24,9 +245,24 @@ where
         {
             rows,
             cols,
-            grid: vec![P::default(); (rows * cols) as usize]
+            viewport: Viewport::default(),
+            grid: vec![P::default(); rows * cols],
+            clipped: 0,
+            carry: None,
         }
     }
+
+    /// [`MyGrid::new`], failing instead of aborting when the grid cannot be allocated
+    pub fn try_new(rows: usize, cols: usize) -> Result<Self, AllocError>
+    {
+        let pixels = rows.checked_mul(cols).ok_or(AllocError::TooLarge { rows, cols })?;
+
+        let mut grid = Vec::new();
+        grid.try_reserve_exact(pixels).map_err(|_| AllocError::OutOfMemory { bytes: pixels.saturating_mul(std::mem::size_of::<P>()) })?;
+        grid.resize(pixels, P::default());
+
+        Ok(MyGrid { rows, cols, viewport: Viewport::default(), grid, clipped: 0, carry: None })
+    }
     
     pub fn apply_all_in_parallel<F>(&mut self, threads: u16, mut f: F)
     where
@@ -56,6 +292,54 @@ where
         .expect("Some thread panicked");
     }
 
+    /// Add one hit to the pixel under `(x, y)`.
+    /// Points outside the viewport are dropped.
+    pub(crate) fn plot(&mut self, x: f64, y: f64) -> ()
+    where
+        P: num_traits::CheckedAdd
+    {
+        let Some(i) = self.pixel_index(x, y) else { return };
+
+        let pixel = &mut self.grid[i];
+        match pixel.checked_add(&P::one())
+        {
+            Some(v) => *pixel = v,
+            None => self.clip(i, 1),
+        }
+    }
+
+    /// Add `hits` to the pixel under `(x, y)`, saturating at the largest value of `P`.
+    pub(crate) fn plot_weighted(&mut self, x: f64, y: f64, hits: u64) -> ()
+    where
+        P: num_traits::CheckedAdd
+    {
+        let Some(i) = self.pixel_index(x, y) else { return };
+
+        let fits = P::from(hits).unwrap_or(P::max_value());
+        self.add_at(i, fits);
+
+        let rest = hits - fits.to_u64().unwrap_or(hits);
+        if rest > 0 { self.clip(i, rest) }
+    }
+
+    /// Add `hits` to pixel `i`, saturating and clipping what does not fit
+    fn add_at(&mut self, i: usize, hits: P) -> ()
+    where
+        P: num_traits::CheckedAdd
+    {
+        let pixel = &mut self.grid[i];
+        match pixel.checked_add(&hits)
+        {
+            Some(v) => *pixel = v,
+            None =>
+            {
+                let excess = excess(*pixel, hits);
+                *pixel = P::max_value();
+                self.clip(i, excess);
+            },
+        }
+    }
+
     pub fn r#static(&mut self) -> () 
     where
         P: num_traits::CheckedAdd
@@ -79,21 +363,239 @@ where
     }
 }
 
+impl<P> MyGrid<P>
+where
+    P: image::Primitive + Default + num_traits::CheckedAdd
+{
+    /// Serial render drawing the transform choices from `rng`.
+    /// [`Fractalize::fractalize`](crate::fractal::Fractalize::fractalize) 
+    /// uses the default [`RngKind`] seeded from the operating system.
+    pub fn fractalize_with_rng<R>(&mut self, num_points: usize, rng: R) -> ()
+    where
+        R: RngCore
+    {
+        self.fractalize_with_choices(num_points, BitStream::new(rng, num_points));
+    }
+
+    /// Serial render taking the transform choices from `choices`,
+    /// e.g. one of the sequences in [`crate::fractal::sequence`].
+    /// Stops early if `choices` runs out.
+    pub fn fractalize_with_choices<I>(&mut self, num_points: usize, choices: I) -> ()
+    where
+        I: IntoIterator<Item = bool>
+    {
+        self.walk(&Transform::default(), (0.5, 0.5), num_points, choices);
+    }
+
+    /// Follow one orbit from `start` for up to `num_points` steps, plotting every step.
+    /// Returns where the orbit ended so a later call can pick it up again.
+    pub fn walk<I>(&mut self, transform: &Transform, start: (f64, f64), num_points: usize, choices: I) -> (f64, f64)
+    where
+        I: IntoIterator<Item = bool>
+    {
+        let (mut x, mut y) = start;
+
+        for s in choices.into_iter().take(num_points)
+        {
+            (x, y) = transform.apply(x, y, s);
+            self.plot(x, y);
+        }
+
+        (x, y)
+    }
+
+    /// Split `num_points` over `threads` independent orbits, each plotting into
+    /// its own grid, and add the grids together at the end.
+    /// Thread `i` draws from `rng` seeded with the `i`th output of SplitMix64 started at `seed`.
+    pub fn fractalize_parallel(
+        &mut self, 
+        num_points: usize, 
+        transform: &Transform, 
+        source: &ChoiceSource,
+        rng: RngKind, 
+        seed: u64, 
+        threads: usize
+    ) -> ()
+    where
+        P: Send
+    {
+        let threads = threads.max(1);
+        let mut seeds = SplitMix64::new(seed);
+        let (rows, cols, viewport) = (self.rows, self.cols, self.viewport);
+
+        let locals: Vec<MyGrid<P>> = 
+        thread::scope(
+        |scope|
+        {
+            let handles: Vec<_> = 
+                (0..threads)
+                .map(
+                |i|
+                {
+                    let share = num_points / threads + usize::from(i < num_points % threads);
+                    let rng = rng.seeded(seeds.next_u64());
+                    scope.spawn(
+                    move ||
+                    {
+                        let mut local = MyGrid::<P>::new(rows, cols).with_viewport(viewport);
+                        local.walk(transform, (0.5, 0.5), share, source.choices(rng, share));
+                        local
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().expect("Some thread panicked")).collect()
+        });
+
+        for local in &locals
+        {
+            self.add_grid(local);
+        }
+    }
+
+    /// Add the counts of `other` to this grid pixel by pixel, 
+    /// saturating at the largest value of `P`. What does not fit is clipped,
+    /// and so is what `other` clipped.
+    /// Panics if the dimensions differ.
+    pub fn add_grid(&mut self, other: &MyGrid<P>) -> ()
+    {
+        assert_eq!((self.rows, self.cols), (other.rows, other.cols), "grid dimensions differ");
+
+        for (i, &b) in other.grid.iter().enumerate()
+        {
+            self.add_at(i, b);
+        }
+
+        self.clipped = self.clipped.saturating_add(other.clipped);
+        if let (Some(carry), Some(other)) = (&mut self.carry, &other.carry)
+        {
+            for (&i, &hits) in other
+            {
+                let c = carry.entry(i).or_default();
+                *c = c.saturating_add(hits);
+            }
+        }
+    }
+
+    /// [`MyGrid::add_grid`], leaving `other` empty for reuse
+    pub fn drain_grid(&mut self, other: &mut MyGrid<P>) -> ()
+    {
+        assert_eq!((self.rows, self.cols), (other.rows, other.cols), "grid dimensions differ");
+
+        for (i, b) in other.grid.iter_mut().enumerate()
+        {
+            self.add_at(i, std::mem::replace(b, P::zero()));
+        }
+
+        self.clipped = self.clipped.saturating_add(std::mem::take(&mut other.clipped));
+        if let (Some(carry), Some(other)) = (&mut self.carry, &mut other.carry)
+        {
+            for (i, hits) in other.drain()
+            {
+                let c = carry.entry(i).or_default();
+                *c = c.saturating_add(hits);
+            }
+        }
+    }
+
+    /// The same counts in pixels of type `Q`, with the carried hits added back.
+    /// Counts `Q` cannot hold saturate and are clipped, carried if this grid was carrying.
+    pub fn widen<Q>(self) -> MyGrid<Q>
+    where
+        Q: image::Primitive
+    {
+        self.try_widen().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// [`MyGrid::widen`], failing instead of aborting if the wider grid cannot be allocated.
+    /// Both grids are held until it returns.
+    pub fn try_widen<Q>(&self) -> Result<MyGrid<Q>, AllocError>
+    where
+        Q: image::Primitive
+    {
+        let MyGrid { rows, cols, viewport, grid, clipped, carry } = self;
+        let max = Q::max_value().to_u64().unwrap_or(u64::MAX);
+
+        let mut counts = Vec::new();
+        counts.try_reserve_exact(grid.len())
+        .map_err(|_| AllocError::OutOfMemory { bytes: grid.len().saturating_mul(std::mem::size_of::<Q>()) })?;
+
+        let carried: u64 = carry.iter().flat_map(|c| c.values()).fold(0, |a, &h| a.saturating_add(h));
+        let mut widened = MyGrid
+        {
+            rows: *rows,
+            cols: *cols,
+            viewport: *viewport,
+            grid: counts,
+            clipped: clipped.saturating_sub(carried),
+            carry: carry.as_ref().map(|_| HashMap::new()),
+        };
+
+        for (i, p) in grid.iter().enumerate()
+        {
+            let extra = carry.as_ref().and_then(|c| c.get(&i)).copied().unwrap_or(0);
+            let count = p.to_u64().unwrap_or(u64::MAX).saturating_add(extra);
+
+            widened.grid.push(Q::from(count.min(max)).unwrap_or(Q::max_value()));
+            if count > max
+            {
+                widened.clip(i, count - max);
+            }
+        }
+
+        Ok(widened)
+    }
+}
+
+/// How far `a + b` goes past the largest value of `P`
+fn excess<P>(a: P, b: P) -> u64
+where
+    P: image::Primitive
+{
+    let max = P::max_value().to_u64().unwrap_or(u64::MAX);
+    a.to_u64().unwrap_or(u64::MAX).saturating_add(b.to_u64().unwrap_or(u64::MAX)).saturating_sub(max)
+}
+
+/// The count at column `x` of row `y`
+impl<P> Index2D<usize, usize> for MyGrid<P>
+{
+    type Output = P;
+
+    fn index_2d(&self, x: usize, y: usize) -> Result<&P, Index2DError> {
+        if x >= self.cols || y >= self.rows
+        {
+            return Err(Index2DError::IndexOutOfBounds(format!("({x}, {y}) on a {}x{} grid", self.cols, self.rows)));
+        }
+        Ok(&self.grid[y * self.cols + x])
+    }
+}
+
+impl<P> IndexMut2D<usize, usize> for MyGrid<P>
+{
+    fn index_mut_2d(&mut self, x: usize, y: usize) -> Result<&mut P, Index2DError> {
+        if x >= self.cols || y >= self.rows
+        {
+            return Err(Index2DError::IndexOutOfBounds(format!("({x}, {y}) on a {}x{} grid", self.cols, self.rows)));
+        }
+        Ok(&mut self.grid[y * self.cols + x])
+    }
+}
+
 impl<T> crate::fractal::Fractalize for MyGrid<T>
 where
-    T: image::Primitive + num_traits::CheckedAdd + Send,
+    T: image::Primitive + Default + num_traits::CheckedAdd + Send,
 {
-    fn fractalize(&mut self, num_points: usize) -> () 
+    fn fractalize(&mut self, num_points: usize) -> Result<(), FractalError> 
     {
-        let distr = 
-            rand::distributions::Uniform::new(0, usize::MAX);
-        let rands: Vec<usize> = rand::thread_rng().sample_iter(&distr).take(num_points / 64).collect();
+        if self.grid.is_empty() { return Err(FractalError::InvalidDimensions { rows: self.rows, cols: self.cols }) }
+
+        // threads that replay the same choices each seed their own stream with this
+        let seed = RngKind::default().from_entropy().next_u64();
 
         let mut x: f64 = 0.5;
         let mut y: f64 = 0.5;
         
-        let rot: f64 = 1.724643921305295;
-        let theta_offset: f64 = 3.0466792337230033;
+        let t = Transform::default();
 
         let rows = self.rows;
         let cols = self.cols;
@@ -101,25 +603,7 @@ where
         let transform = 
         move |x: f64, y: f64, s: bool| -> (f64, f64)
         {
-            let (x, y) = 
-            if s
-            {
-                (
-                    x * rot.cos() + y * rot.sin(),
-                    y * rot.cos() - x * rot.sin()
-                )
-            }
-            else
-            {
-                let rad = x * 0.5 + 0.5;
-                let theta = y * PI + theta_offset;
-                (
-                    rad * theta.cos(),
-                    rad * theta.sin()
-                )
-            };
-
-            (x, y)
+            t.apply(x, y, s)
         };
 
         let xy_to_grid_loc =
@@ -128,7 +612,7 @@ where
             let r = (y / 2.0 + 0.5) * rows as f64;
             let c = (x / 2.0 + 0.5) * cols as f64;
 
-            return (r as usize, c as usize);
+            (r as usize, c as usize)
         };
 
         let flat_index =
@@ -144,20 +628,19 @@ where
 
             for i in 0..4
             {
-                let angle = 2.0 * PI / 4_f64 * i as f64;
+                let _angle = 2.0 * PI / 4_f64 * i as f64;
                 // let (mut x, mut y) = 0.5 * (2.0 * PI / 4 as f64)
                 // let (mut x, mut y) = (0.75 * angle.cos(), 0.1 * angle.sin());
                 let (mut x, mut y) = (0.5, 0.5);
             
     
                 let sxi = sx.clone();
-                let handle = std::thread::spawn(
+                let _handle = std::thread::spawn(
                 move ||
                 {
-                    let mut rng = thread_rng();
-                    for _ in 0..(num_points/4)
+                    let share = num_points / 4 + usize::from(i < num_points % 4);
+                    for b in BitStream::new(RngKind::default().from_entropy(), share)
                     {
-                        let b = (rng.sample(distr) & 1) == 0;
                         (x, y) = transform(x, y, b);
                         let (r, c) = xy_to_grid_loc(x, y);
                         let _ = sxi.send(flat_index(r, c));
@@ -195,16 +678,12 @@ where
 
             let num_threads = 4;
     
-            let slice = rands.as_slice();
-            let chunk_size_exact = rands.len() / num_threads;
             std::thread::scope(
             |scope|
             {
-                slice
-                .chunks_exact(chunk_size_exact)
-                .enumerate()
+                (0..num_threads)
                 .for_each(
-                |(i, sub_slice)|
+                |i|
                 {
                     let sxi = sx.clone();
                     scope.spawn(
@@ -212,16 +691,14 @@ where
                     {
                         let angle = 2.0 * PI / (num_threads as f64) * (i as f64);
                         let (mut x, mut y) = (0.5 * angle.cos(), 0.5 * angle.sin());
-                        for this_rand in sub_slice
+
+                        let share = num_points / num_threads + usize::from(i < num_points % num_threads);
+                        let rng = RngKind::default().seeded(seed.wrapping_add(i as u64));
+                        for this_bool in BitStream::new(rng, share)
                         {
-                            for i in 0..64
-                            {
-                                let this_bool: bool = (this_rand & (1 << i)) != 0;
-                                
-                                (x, y) = transform(x, y, this_bool);
-                                let (r, c) = xy_to_grid_loc(x, y);
-                                let _ = sxi.send(flat_index(r, c));
-                            }
+                            (x, y) = transform(x, y, this_bool);
+                            let (r, c) = xy_to_grid_loc(x, y);
+                            let _ = sxi.send(flat_index(r, c));
                         }
                     });
                 })
@@ -261,27 +738,23 @@ where
                 {
                     // every chunk looks at all rands and checks if the output x-y val is in the valid range
                     // Technically some redundant effort but Paul did this for cache optimization reasons.
-                    let rrr = &rands;
+                    // Each chunk replays the same choices from an identically seeded stream.
                     scope.spawn(
                     move ||
                     {
                         let valid_indices = (en * chunk_exact_size)..((en+1) * chunk_exact_size);
-                        for r in rrr
+                        for b in BitStream::new(RngKind::default().seeded(seed), num_points)
                         {
-                            for i in 0..64_usize
-                            {
-                                let b: bool = (r & (1 << i)) != 0;
-                                (x, y) = transform(x, y, b);
-                                let (r, c) = xy_to_grid_loc(x, y);
-    
-                                let index = flat_index(r, c);
-    
-                                if !valid_indices.contains(&index) { continue }
-    
-                                let translated_index = index - valid_indices.start;
-    
-                                sub_slice[translated_index] = sub_slice[translated_index] + T::one();
-                            }
+                            (x, y) = transform(x, y, b);
+                            let (r, c) = xy_to_grid_loc(x, y);
+
+                            let index = flat_index(r, c);
+
+                            if !valid_indices.contains(&index) { continue }
+
+                            let translated_index = index - valid_indices.start;
+
+                            sub_slice[translated_index] = sub_slice[translated_index] + T::one();
                         }
                     });
                 })
@@ -289,32 +762,14 @@ where
         };
 
         // no parallelization
-        let _default =
+        let mut _default =
         ||
         {
-            for r in rands
-            {
-                for i in 0..64_usize
-                {
-                    let this_r = r & (1 << i);
-            
-                    (x, y) = transform(x, y, this_r != 0);
-        
-                    let (r, c) = xy_to_grid_loc(x, y);
-        
-                    if let Some(pixel) = self.grid.get_mut(flat_index(r, c))
-                    {
-                        *pixel = match pixel.checked_add(&T::one())
-                        {
-                            Some(v) => v,
-                            None => *pixel
-                        }
-                    }
-                }
-            }
+            self.fractalize_with_rng(num_points, RngKind::default().from_entropy());
         };
 
         _default();
+        Ok(())
     }
 }
 
@@ -355,12 +810,12 @@ impl<P> From<MyGridPar<P>> for MyGrid<P>
     }
 }
 
-impl<P> Into<MyGreyImage<P>> for MyGridPar<P>
+impl<P> From<MyGridPar<P>> for MyGreyImage<P>
 where
     P: image::Primitive
 {
-    fn into(self) -> MyGreyImage<P> {
-        self.0.into()
+    fn from(value: MyGridPar<P>) -> Self {
+        value.0.into()
     }
 }
 
@@ -368,18 +823,21 @@ impl crate::fractal::Fractalize for MyGridPar<u8>
 // where
 //     P: image::Primitive + num_traits::CheckedAdd + Add + Send,
 {
-    fn fractalize(&mut self, num_points: usize) -> () 
+    fn fractalize(&mut self, num_points: usize) -> Result<(), FractalError> 
     {
+        if self.grid.is_empty() { return Err(FractalError::InvalidDimensions { rows: self.rows, cols: self.cols }) }
+
         // Strategy: Create a sparse matrix and use that in each thread
         // Upon thread join, add the matrices, convert 
 
         let num_threads = 4;
-        let matrix_size = (self.rows as usize, self.cols as usize);
+        let matrix_size = (self.rows, self.cols);
 
         let mut handles = vec![];
 
-        for _ in 0..num_threads
+        for i in 0..num_threads
         {
+            let share = num_points / num_threads + usize::from(i < num_points % num_threads);
             let handle = thread::spawn(
                 move ||
                 {
@@ -387,23 +845,17 @@ impl crate::fractal::Fractalize for MyGridPar<u8>
                     let mut local_matrix: sprs::CsMat<u8> = 
                         sprs::CsMatBase::zero(matrix_size);
                     
-                    // assumes square
-                    let distr = rand::distributions::Uniform::new(0, matrix_size.0);
-                    let mut rng = rand::thread_rng();
-
                     let mut x: f64 = 0.0;
                     let mut y: f64 = 0.5;
 
                     let rot: f64 = 1.724643921305295;
                     let theta_offset: f64 = 3.0466792337230033;
 
-                    for _ in 0..(num_points / num_threads)
+                    for s in BitStream::new(RngKind::default().from_entropy(), share)
                     {
                         // if ii % 100_000 == 0 { println!("{ii} in thread {i}"); }
-                        let this_rand = distr.sample(&mut rng);
-
                         (x, y) = 
-                        if this_rand & 1 == 1
+                        if s
                         {
                             (
                                 x * rot.cos() + y * rot.sin(),
@@ -469,23 +921,24 @@ impl crate::fractal::Fractalize for MyGridPar<u8>
             let (ind_a, ind_b) = (indptr.index(row), indptr.index(row+1));
             for (col, val) in indices[ind_a..ind_b].iter().zip(data[ind_a..ind_b].iter())
             {
-                self.grid[row * matrix_size.1 as usize + col] = *val;
+                self.grid[row * matrix_size.1 + col] = *val;
             }
         }
+        Ok(())
     }
 }
 
 pub type MyGreyImage<P> = image::ImageBuffer<image::Luma<P>, Vec<P>>;
 
 /// Conversion to a grey image
-impl<P> Into<MyGreyImage<P>> for MyGrid<P>
+impl<P> From<MyGrid<P>> for MyGreyImage<P>
 where
     P: image::Primitive
 { 
-    fn into(self) -> MyGreyImage<P> {
+    fn from(value: MyGrid<P>) -> Self {
         // from_raw fails if the buffer is not large enough.
         // But we know the buffer will have the right size so it will not fail 
-        MyGreyImage::from_raw(self.rows as u32, self.cols as u32, self.grid).unwrap()
+        MyGreyImage::from_raw(value.rows as u32, value.cols as u32, value.grid).unwrap()
     }
 }
 
@@ -499,17 +952,17 @@ mod test
     {
         use crate::fractal::Fractalize;
         let mut img = super::MyGrid::<u8>::new(1024, 1024);
-        img.fractalize(10_000_000);
+        img.fractalize(10_000_000).unwrap();
 
         let img: super::MyGreyImage<_> = img.into();
-        let _ = img.save("image_a.png");
+        img.save("image_a.png").unwrap();
     }
 
     #[test]
     fn slice_chunks_even()
     {
         let b = Vec::from_iter(1..=20);
-        let mut it = (&*b).chunks(5);
+        let mut it = b.chunks(5);
         
         assert_eq!(Some(&[1, 2, 3, 4, 5][..]),      it.next());
         assert_eq!(Some(&[6, 7, 8, 9, 10][..]),     it.next());
@@ -549,4 +1002,55 @@ mod test
         let img: MyGrid<u16> = MyGrid::<u16>::new(128, 128);
         let _img: MyGreyImage<u16> = img.into();
     }
+    #[test]
+    fn index_2d_and_fractalize_check_the_dimensions()
+    {
+        use crate::{error::FractalError, fractal::{Fractalize, Index2D, IndexMut2D}, my_grid::MyGrid};
+
+        let mut g = MyGrid::<u8>::new(2, 3);
+        *g.index_mut_2d(2, 1).unwrap() = 7;
+        assert_eq!(g.as_slice()[5], 7);
+        assert!(g.index_2d(3, 0).is_err());
+        assert!(g.index_2d(0, 2).is_err());
+
+        let mut empty = MyGrid::<u8>::new(0, 4);
+        assert!(matches!(empty.fractalize(100), Err(FractalError::InvalidDimensions { rows: 0, cols: 4 })));
+    }
+
+    #[test]
+    fn overflow_is_clipped_or_carried_into_a_wider_grid()
+    {
+        use crate::my_grid::MyGrid;
+
+        let mut g = MyGrid::<u8>::new(2, 2);
+        for _ in 0..300 { g.plot(-0.5, 0.5) }
+        g.plot_weighted(0.5, 0.5, 1000);
+        assert_eq!((g.clipped(), g.has_carry()), (45 + 745, false));
+
+        let mut other = MyGrid::<u8>::new(2, 2);
+        other.plot_weighted(0.5, 0.5, 10);
+        g.add_grid(&other);
+        assert_eq!(g.clipped(), 45 + 745 + 10);
+
+        let mut g = MyGrid::<u8>::new(2, 2);
+        g.start_carrying();
+        for _ in 0..300 { g.plot(-0.5, 0.5) }
+        g.plot_weighted(0.5, 0.5, 1000);
+        assert!(g.has_carry());
+
+        // a checkpoint taken before widening keeps what was carried
+        let mut g: MyGrid<u8> = postcard::from_bytes(&postcard::to_allocvec(&g).unwrap()).unwrap();
+        assert!(g.has_carry());
+
+        let mut drained = MyGrid::<u8>::new(2, 2);
+        drained.start_carrying();
+        drained.drain_grid(&mut g);
+        assert_eq!((g.clipped(), g.has_carry(), g.as_slice()), (0, false, &[0; 4][..]));
+
+        let wide: MyGrid<u16> = drained.widen();
+        assert_eq!((wide.clipped(), wide.has_carry()), (0, false));
+        let mut counts = wide.as_slice().to_vec();
+        counts.sort();
+        assert_eq!(counts, [0, 0, 300, 1000]);
+    }
 }
\ No newline at end of file
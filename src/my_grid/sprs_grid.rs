@@ -1,17 +1,18 @@
 use std::f64::consts::PI;
 
-use rand::prelude::*;
+use crate::{error::FractalError, fractal::{bits::BitStream, rng::RngKind}};
 
 use super::MyGrid;
 
 // FAR TOO SLOW
 impl crate::fractal::Fractalize for sprs::CsMat<u8>
 {
-    fn fractalize(&mut self, num_points: usize) -> () 
+    fn fractalize(&mut self, num_points: usize) -> Result<(), FractalError> 
     {
-        let distr = 
-            rand::distributions::Uniform::new(0, self.rows());
-        let mut rng = rand::thread_rng();
+        if self.rows() == 0 || self.cols() == 0
+        {
+            return Err(FractalError::InvalidDimensions { rows: self.rows(), cols: self.cols() });
+        }
 
         let mut x: f64 = 0.0;
         let mut y: f64 = 0.5;
@@ -19,14 +20,10 @@ impl crate::fractal::Fractalize for sprs::CsMat<u8>
         let rot: f64 = 1.724643921305295;
         let theta_offset: f64 = 3.0466792337230033;
 
-        for _ in 0..num_points
+        for s in BitStream::new(RngKind::default().from_entropy(), num_points)
         {
-            let this_rand = distr.sample(&mut rng);
-
-            // Check whether these are the same. 
-            // I believe this case would be identical
             (x, y) = 
-            if this_rand & 1 == 1
+            if s
             {
                 (
                     x * rot.cos() + y * rot.sin(),
@@ -61,11 +58,13 @@ impl crate::fractal::Fractalize for sprs::CsMat<u8>
                 },
             }
         }
+        Ok(())
     }
 }
 
 impl From<sprs::CsMat<u8>> for MyGrid<u8>
 {
+    #[allow(clippy::unnecessary_cast)]
     fn from(value: sprs::CsMat<u8>) -> Self {
         // read sparse matrix data into self.grid
         let indptr = value.indptr();
@@ -84,6 +83,6 @@ impl From<sprs::CsMat<u8>> for MyGrid<u8>
             }
         }
 
-        MyGrid { rows: value.rows(), cols: value.cols(), grid }
+        MyGrid { rows: value.rows(), cols: value.cols(), viewport: Default::default(), grid, clipped: 0, carry: None }
     }
 }
\ No newline at end of file
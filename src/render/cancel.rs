@@ -0,0 +1,27 @@
+//! Stopping renders from outside.
+
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+/// Shared flag a render polls while it runs.
+/// Clones share the flag, so one can be handed to another thread,
+/// a signal handler or a UI, and cancelling any of them stops the render.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken
+{
+    pub fn new() -> Self
+    {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) -> ()
+    {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool
+    {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,146 @@
+//! Snapshots of unfinished renders.
+//!
+//! Laid out like a [`Histogram`](super::histogram::Histogram) file, with
+//! its own header and the [`RenderState`] of the session after the counts.
+
+use std::{fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{histogram::{frame, unframe, write_atomically}, scene::Scene, session::RenderState};
+use crate::my_grid::dyn_grid::DynGrid;
+
+const MAGIC: &[u8; 8] = b"RFCKPT\0\0";
+
+/// Bumped when the layout of [`Checkpoint`] or [`RenderState`] changes after a release
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Everything [`Session::resume`](super::session::Session::resume) needs to carry on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint
+{
+    pub scene: Scene,
+    pub grid: DynGrid,
+    pub state: RenderState,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError
+{
+    Io(std::io::Error),
+    Encoding(postcard::Error),
+    /// The file does not start with the checkpoint header
+    NotACheckpoint,
+    UnsupportedVersion(u32),
+    /// The grid or the state does not fit the scene
+    Inconsistent(String),
+}
+
+impl Display for CheckpointError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            CheckpointError::Io(e) => write!(f, "checkpoint file: {e}"),
+            CheckpointError::Encoding(e) => write!(f, "checkpoint encoding: {e}"),
+            CheckpointError::NotACheckpoint => write!(f, "not a checkpoint file"),
+            CheckpointError::UnsupportedVersion(v) =>
+                write!(f, "unsupported checkpoint version {v}, this build reads version {CHECKPOINT_VERSION}"),
+            CheckpointError::Inconsistent(e) => write!(f, "inconsistent checkpoint: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<std::io::Error> for CheckpointError
+{
+    fn from(value: std::io::Error) -> Self {
+        CheckpointError::Io(value)
+    }
+}
+
+impl From<postcard::Error> for CheckpointError
+{
+    fn from(value: postcard::Error) -> Self {
+        CheckpointError::Encoding(value)
+    }
+}
+
+impl Checkpoint
+{
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CheckpointError>
+    {
+        Ok(frame(MAGIC, CHECKPOINT_VERSION, self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Checkpoint, CheckpointError>
+    {
+        let (version, body) = unframe(MAGIC, bytes).ok_or(CheckpointError::NotACheckpoint)?;
+        if version != CHECKPOINT_VERSION
+        {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+
+        let c: Checkpoint = postcard::from_bytes(body)?;
+        c.check()?;
+        Ok(c)
+    }
+
+    /// Replaces `path` in one step, so an interrupted save leaves the previous checkpoint intact
+    pub fn save<Q>(&self, path: Q) -> Result<(), CheckpointError>
+    where
+        Q: AsRef<Path>
+    {
+        Ok(write_atomically(path.as_ref(), &self.to_bytes()?)?)
+    }
+
+    pub fn load<Q>(path: Q) -> Result<Checkpoint, CheckpointError>
+    where
+        Q: AsRef<Path>
+    {
+        Checkpoint::from_bytes(&std::fs::read(path)?)
+    }
+
+    fn check(&self) -> Result<(), CheckpointError>
+    {
+        let r = &self.scene.render;
+        if (self.grid.rows(), self.grid.cols()) != (r.rows, r.cols)
+        {
+            return Err(CheckpointError::Inconsistent(format!(
+                "grid is {}x{} but its scene is {}x{}",
+                self.grid.cols(), self.grid.rows(), r.cols, r.rows
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{Checkpoint, CheckpointError};
+    use crate::render::{histogram::Histogram, scene::Scene, session::Session};
+
+    #[test]
+    fn save_replaces_and_load_reads_back()
+    {
+        let mut scene = Scene::default();
+        (scene.render.rows, scene.render.cols, scene.render.points) = (16, 16, Some(10_000));
+
+        let mut s = Session::<u32>::new(scene);
+        let path = std::env::temp_dir().join(format!("rf-checkpoint-{}.bin", std::process::id()));
+
+        s.advance(100);
+        s.checkpoint().save(&path).unwrap();
+        s.advance(100);
+        let c = s.checkpoint();
+        c.save(&path).unwrap();
+
+        assert_eq!(Checkpoint::load(&path).unwrap(), c);
+        std::fs::remove_file(&path).unwrap();
+
+        let h = Histogram::new(c.scene.clone(), c.grid.clone()).to_bytes().unwrap();
+        assert!(matches!(Checkpoint::from_bytes(&h), Err(CheckpointError::NotACheckpoint)));
+    }
+}
@@ -0,0 +1,102 @@
+//! Knowing when more points stop paying off.
+
+use crate::my_grid::MyGrid;
+
+/// Settings for stopping a render once the image settles.
+///
+/// The image is snapshotted, as a density summing to one, each time the
+/// number of points doubles. The relative L2 difference between two
+/// snapshots is about the noise left in the earlier one, so it falls
+/// roughly like one over the square root of the points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Convergence
+{
+    /// Stop once the relative change between snapshots is below this
+    pub tolerance: f64,
+    /// Points plotted before the first snapshot
+    pub first_snapshot: usize,
+}
+
+impl Default for Convergence
+{
+    fn default() -> Self
+    {
+        Convergence { tolerance: 0.01, first_snapshot: 1 << 20 }
+    }
+}
+
+/// The counts scaled to sum to one, all zero for an empty grid
+pub fn density<P>(grid: &MyGrid<P>) -> Vec<f64>
+where
+    P: image::Primitive
+{
+    let counts: Vec<f64> = grid.as_slice().iter().map(|p| p.to_f64().unwrap_or(0.0)).collect();
+    let total: f64 = counts.iter().sum();
+    let scale = if total > 0.0 { 1.0 / total } else { 0.0 };
+
+    counts.into_iter().map(|c| c * scale).collect()
+}
+
+/// `|new - old| / |new|` in the L2 norm, zero when both are empty
+pub fn relative_l2(old: &[f64], new: &[f64]) -> f64
+{
+    let diff: f64 = old.iter().zip(new).map(|(a, b)| (b - a) * (b - a)).sum();
+    let norm: f64 = new.iter().map(|b| b * b).sum();
+
+    if norm > 0.0 { (diff / norm).sqrt() }
+    else if diff > 0.0 { f64::INFINITY }
+    else { 0.0 }
+}
+
+/// Snapshots taken while a render runs
+pub(super) struct Tracker
+{
+    settings: Convergence,
+    next: usize,
+    last: Option<Vec<f64>>,
+    /// Measured at the latest snapshot
+    pub change: Option<f64>,
+}
+
+impl Tracker
+{
+    pub fn new(settings: Convergence, points_done: usize) -> Self
+    {
+        Tracker { settings, next: settings.first_snapshot.max(points_done).max(1), last: None, change: None }
+    }
+
+    /// Points to plot before the next snapshot
+    pub fn until_next(&self, points_done: usize) -> usize
+    {
+        self.next.saturating_sub(points_done)
+    }
+
+    /// Take a snapshot if one is due, returning whether the image has settled
+    pub fn check<P>(&mut self, grid: &MyGrid<P>, points_done: usize) -> bool
+    where
+        P: image::Primitive
+    {
+        if points_done < self.next { return false }
+
+        let now = density(grid);
+        self.change = self.last.as_deref().map(|last| relative_l2(last, &now));
+        self.last = Some(now);
+        self.next = points_done.saturating_mul(2);
+
+        self.change.is_some_and(|c| c < self.settings.tolerance)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::relative_l2;
+
+    #[test]
+    fn relative_l2_of_simple_densities()
+    {
+        assert_eq!(relative_l2(&[0.5, 0.5], &[0.5, 0.5]), 0.0);
+        assert_eq!(relative_l2(&[0.0, 0.0], &[0.0, 0.0]), 0.0);
+        assert!((relative_l2(&[1.0, 0.0], &[0.0, 1.0]) - 2_f64.sqrt()).abs() < 1e-12);
+    }
+}
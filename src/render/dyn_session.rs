@@ -0,0 +1,249 @@
+//! Sessions whose pixel type is picked at runtime, and can grow.
+
+use std::time::Instant;
+
+use super::{
+    checkpoint::Checkpoint, memory::MemoryEstimate, scene::Scene,
+    session::{Outcome, Overflow, RenderState, RunOptions, Session}
+};
+use crate::{error::FractalError, my_grid::dyn_grid::{DynGrid, PixelType}};
+
+/// A [`Session`] of any pixel type
+pub enum DynSession
+{
+    U8(Session<u8>),
+    U16(Session<u16>),
+    U32(Session<u32>),
+    U64(Session<u64>),
+}
+
+/// Run `$body` with `$s` bound to the session inside `$dyn`, whatever its pixel type
+macro_rules! with_session {
+    ($dyn:expr, $s:ident => $body:expr) => {
+        match $dyn
+        {
+            DynSession::U8($s) => $body,
+            DynSession::U16($s) => $body,
+            DynSession::U32($s) => $body,
+            DynSession::U64($s) => $body,
+        }
+    };
+}
+
+impl DynSession
+{
+    /// A render of `scene` into an empty grid of `pixel_type`
+    pub fn new(scene: Scene, pixel_type: PixelType) -> Self
+    {
+        match pixel_type
+        {
+            PixelType::U8 => DynSession::U8(Session::new(scene)),
+            PixelType::U16 => DynSession::U16(Session::new(scene)),
+            PixelType::U32 => DynSession::U32(Session::new(scene)),
+            PixelType::U64 => DynSession::U64(Session::new(scene)),
+        }
+    }
+
+    /// [`Session::try_alloc`] into a grid of `pixel_type`.
+    /// Set [`RunOptions::memory_budget`] to check the wider types too.
+    pub fn try_alloc(scene: Scene, pixel_type: PixelType) -> Result<Self, FractalError>
+    {
+        Ok(match pixel_type
+        {
+            PixelType::U8 => DynSession::U8(Session::try_alloc(scene)?),
+            PixelType::U16 => DynSession::U16(Session::try_alloc(scene)?),
+            PixelType::U32 => DynSession::U32(Session::try_alloc(scene)?),
+            PixelType::U64 => DynSession::U64(Session::try_alloc(scene)?),
+        })
+    }
+
+    /// [`Session::resume`] with the pixel type of the checkpoint
+    pub fn resume(checkpoint: Checkpoint) -> Result<Self, FractalError>
+    {
+        Ok(match checkpoint.grid.pixel_type()
+        {
+            PixelType::U8 => DynSession::U8(Session::resume(checkpoint)?),
+            PixelType::U16 => DynSession::U16(Session::resume(checkpoint)?),
+            PixelType::U32 => DynSession::U32(Session::resume(checkpoint)?),
+            PixelType::U64 => DynSession::U64(Session::resume(checkpoint)?),
+        })
+    }
+
+    pub fn pixel_type(&self) -> PixelType
+    {
+        match self
+        {
+            DynSession::U8(_) => PixelType::U8,
+            DynSession::U16(_) => PixelType::U16,
+            DynSession::U32(_) => PixelType::U32,
+            DynSession::U64(_) => PixelType::U64,
+        }
+    }
+
+    pub fn scene(&self) -> &Scene
+    {
+        with_session!(self, s => s.scene())
+    }
+
+    pub fn overflow(&self) -> Overflow
+    {
+        with_session!(self, s => s.overflow())
+    }
+
+    pub fn state(&self) -> RenderState
+    {
+        with_session!(self, s => s.state())
+    }
+
+    pub fn points_done(&self) -> usize
+    {
+        with_session!(self, s => s.points_done())
+    }
+
+    pub fn points_total(&self) -> Option<usize>
+    {
+        with_session!(self, s => s.points_total())
+    }
+
+    pub fn is_done(&self) -> bool
+    {
+        with_session!(self, s => s.is_done())
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint
+    {
+        with_session!(self, s => s.checkpoint())
+    }
+
+    pub fn into_grid(self) -> DynGrid
+    {
+        with_session!(self, s => s.into_grid().into())
+    }
+
+    /// Move to the next wider pixel type, see [`Session::widen`].
+    /// A `u64` session stays as it is, and one that fails to widen is left as it was.
+    pub fn widen(&mut self) -> Result<(), FractalError>
+    {
+        *self = match self
+        {
+            DynSession::U8(s) => DynSession::U16(s.take_widened()?),
+            DynSession::U16(s) => DynSession::U32(s.take_widened()?),
+            DynSession::U32(s) => DynSession::U64(s.take_widened()?),
+            DynSession::U64(_) => return Ok(()),
+        };
+        Ok(())
+    }
+
+    /// Whether widening fits in `budget` bytes, see [`MemoryEstimate::of_widening`]
+    fn check_widening(&self, budget: u64, converging: bool) -> Result<(), FractalError>
+    {
+        let Some(wider) = self.pixel_type().wider() else { return Ok(()) };
+
+        let estimate = MemoryEstimate::of_widening(self.scene(), self.pixel_type().size(), wider.size())?;
+        if converging { estimate.with_convergence() } else { estimate }.check(budget)?;
+        Ok(())
+    }
+
+    /// [`Session::run`], widening the grid whenever a pixel overflows with [`Overflow::Widen`],
+    /// without losing a hit. A `u64` grid clips as usual. The budget covers the whole run,
+    /// while convergence starts over with each widening. Fails with [`FractalError::Memory`]
+    /// if a widening would go over [`RunOptions::memory_budget`] or cannot be allocated.
+    pub fn run(&mut self, options: &mut RunOptions) -> Result<Outcome, FractalError>
+    {
+        let (start, budget, overflow) = (Instant::now(), options.budget, options.overflow);
+
+        let outcome = loop
+        {
+            options.budget = budget.map(|b| b.saturating_sub(start.elapsed()));
+            // all a u64 grid can do, and what its checkpoints then record
+            if overflow == Overflow::Widen && self.pixel_type() == PixelType::U64 { options.overflow = Overflow::Clip }
+
+            match with_session!(self, s => s.run(options))
+            {
+                Ok(Outcome::Overflowed) =>
+                {
+                    let checked = match options.memory_budget
+                    {
+                        Some(b) => self.check_widening(b, options.converge.is_some()),
+                        None => Ok(()),
+                    };
+                    if let Err(e) = checked.and_then(|()| self.widen()) { break Err(e) }
+                },
+                other => break other,
+            }
+        };
+
+        (options.budget, options.overflow) = (budget, overflow);
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::DynSession;
+    use crate::{
+        error::FractalError,
+        my_grid::dyn_grid::PixelType,
+        render::{memory::{MemoryError, MemoryEstimate}, scene::Scene, session::{Outcome, Overflow, RunOptions}, Strategy}
+    };
+
+    #[test]
+    fn widening_keeps_every_hit()
+    {
+        for strategy in [Strategy::Serial, Strategy::Parallel, Strategy::Exhaustive]
+        {
+            let mut scene = Scene::default();
+            (scene.render.rows, scene.render.cols, scene.render.points) = (8, 8, Some(100_000));
+            (scene.render.seed, scene.render.threads, scene.render.strategy) = (4, 2, strategy);
+
+            let wide = scene.render.render::<u64>().unwrap();
+
+            let mut narrow = DynSession::new(scene.clone(), PixelType::U8);
+            let outcome = narrow.run(&mut RunOptions { overflow: Overflow::Widen, ..Default::default() }).unwrap();
+            assert_eq!(outcome, Outcome::Completed);
+            let max = wide.as_slice().iter().copied().max().unwrap();
+            assert!(max > 255);
+            assert_eq!(narrow.pixel_type(), PixelType::U8.widened_for(max), "{strategy}");
+
+            let grid = narrow.into_grid();
+            assert_eq!(grid.clipped(), 0);
+            assert_eq!(grid.counts(), wide.as_slice(), "{strategy}");
+
+            let mut clipped = DynSession::new(scene, PixelType::U8);
+            clipped.run(&mut RunOptions::default()).unwrap();
+            let grid = clipped.into_grid();
+            let lost = wide.as_slice().iter().sum::<u64>() - grid.counts().iter().sum::<u64>();
+            assert_eq!(grid.clipped(), lost, "{strategy}");
+        }
+    }
+
+    #[test]
+    fn failing_on_overflow_reports_the_clipped_hits()
+    {
+        let mut scene = Scene::default();
+        (scene.render.rows, scene.render.cols, scene.render.points, scene.render.seed) = (8, 8, Some(100_000), 4);
+
+        let mut s = DynSession::new(scene, PixelType::U8);
+        let result = s.run(&mut RunOptions { overflow: Overflow::Fail, ..Default::default() });
+        assert!(matches!(result, Err(FractalError::Overflow { clipped, .. }) if clipped > 0));
+    }
+
+    #[test]
+    fn widening_past_the_memory_budget_fails()
+    {
+        let mut scene = Scene::default();
+        (scene.render.rows, scene.render.cols, scene.render.points, scene.render.seed) = (8, 8, Some(100_000), 4);
+        let budget = MemoryEstimate::of(&scene, 1).unwrap().total();
+
+        let mut s = DynSession::try_alloc(scene, PixelType::U8).unwrap();
+        let mut options = RunOptions { overflow: Overflow::Widen, memory_budget: Some(budget), ..Default::default() };
+        let result = s.run(&mut options);
+        assert!(matches!(result, Err(FractalError::Memory(MemoryError::OverBudget { .. }))));
+
+        // left as it was, so it can still be finished by clipping
+        assert_eq!(s.pixel_type(), PixelType::U8);
+        assert_eq!(options.overflow, Overflow::Widen);
+        assert_eq!(s.run(&mut RunOptions::default()).unwrap(), Outcome::Completed);
+    }
+}
@@ -0,0 +1,161 @@
+//! Picking the number of points from how bright the image should be.
+//!
+//! How many points a given brightness takes depends on how much of the
+//! grid the attractor covers, which depends on the transform, the
+//! viewport and the resolution. A pilot run on a coarser grid measures
+//! the coverage there, and the coverage of the same run at half that
+//! resolution gives the box-counting dimension to scale it up with.
+
+use std::{fmt::Display, str::FromStr};
+
+use super::{RenderSettings, Strategy};
+use crate::error::FractalError;
+
+/// Points plotted by the pilot run
+const PILOT: usize = 1 << 21;
+
+/// Average pilot hits per pixel of the pilot grid
+const PILOT_HITS: usize = 32;
+
+/// Target brightness of a render, over the pixels the attractor covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exposure
+{
+    MeanHits(f64),
+    MedianHits(f64),
+}
+
+impl Display for Exposure
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            Exposure::MeanHits(h) => write!(f, "mean:{h}"),
+            Exposure::MedianHits(h) => write!(f, "median:{h}"),
+        }
+    }
+}
+
+impl FromStr for Exposure
+{
+    type Err = String;
+
+    /// `mean:<hits>` or `median:<hits>`, a bare number being a mean
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, hits) = s.split_once(':').unwrap_or(("mean", s));
+
+        let hits = match hits.trim().parse::<f64>()
+        {
+            Ok(h) if h > 0.0 && h.is_finite() => h,
+            Ok(_) => return Err("the hits per pixel must be positive".to_string()),
+            Err(e) => return Err(format!("invalid hits per pixel '{hits}': {e}")),
+        };
+
+        match kind.trim().to_ascii_lowercase().as_str()
+        {
+            "mean" => Ok(Exposure::MeanHits(hits)),
+            "median" => Ok(Exposure::MedianHits(hits)),
+            _ => Err(format!("unknown exposure '{kind}', expected mean or median")),
+        }
+    }
+}
+
+impl RenderSettings
+{
+    /// Estimate the points giving `exposure` at the configured size with a pilot run
+    /// of a couple of million points, and use that many. Returns the number of points.
+    /// Fails if the pilot run finds nothing in the viewport.
+    pub fn expose(&mut self, exposure: Exposure) -> Result<usize, FractalError>
+    {
+        let pixels = PILOT / PILOT_HITS;
+
+        // the coarsest power of two reduction keeping the pilot grid within `pixels`
+        let mut k = 1;
+        while (self.rows / k) * (self.cols / k) > pixels && self.rows / k > 1 && self.cols / k > 1
+        {
+            k *= 2;
+        }
+
+        let pilot = RenderSettings
+        {
+            rows: self.rows / k,
+            cols: self.cols / k,
+            points: Some(PILOT),
+            strategy: Strategy::Ensemble,
+            ..self.clone()
+        };
+        let grid = pilot.render::<u32>()?;
+
+        let mut hits: Vec<u32> = grid.as_slice().iter().copied().filter(|&h| h > 0).collect();
+        if hits.is_empty()
+        {
+            return Err(FractalError::invalid("viewport", "holds none of the points of the pilot run"));
+        }
+
+        let covered = hits.len() as f64;
+        let in_view = hits.iter().map(|&h| h as f64).sum::<f64>();
+
+        // coverage at half the pilot resolution
+        let (rows, cols) = (pilot.rows.div_ceil(2), pilot.cols.div_ceil(2));
+        let mut half = vec![false; rows * cols];
+        for (i, &h) in grid.as_slice().iter().enumerate()
+        {
+            if h > 0 { half[(i / pilot.cols / 2) * cols + (i % pilot.cols) / 2] = true }
+        }
+        let covered_half = half.iter().filter(|&&c| c).count() as f64;
+
+        let dimension = (covered / covered_half).log2().clamp(0.0, 2.0);
+        let covered_full = covered * (k as f64).powf(dimension);
+
+        let mean = in_view / covered;
+        let target = match exposure
+        {
+            Exposure::MeanHits(h) => h,
+            Exposure::MedianHits(h) =>
+            {
+                let mid = hits.len() / 2;
+                let median = *hits.select_nth_unstable(mid).1 as f64;
+                h * mean / median
+            },
+        };
+
+        // some points land outside the viewport
+        let points = target * covered_full * (PILOT as f64 / in_view);
+        let points = points.min(usize::MAX as f64).round() as usize;
+        self.points = Some(points);
+
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::Exposure;
+    use crate::render::RenderSettings;
+
+    #[test]
+    fn mean_hits_come_out_near_the_target_at_any_size()
+    {
+        for size in [128, 512]
+        {
+            let mut settings = RenderSettings { rows: size, cols: size, seed: 3, ..Default::default() };
+            let points = settings.expose(Exposure::MeanHits(16.0)).unwrap();
+
+            let grid = settings.render::<u32>().unwrap();
+            let covered: Vec<f64> = grid.as_slice().iter().filter(|&&h| h > 0).map(|&h| h as f64).collect();
+            let mean = covered.iter().sum::<f64>() / covered.len() as f64;
+
+            assert!((12.0..20.0).contains(&mean), "{size}: {points} points gave {mean} hits");
+        }
+    }
+
+    #[test]
+    fn parses_mean_and_median()
+    {
+        assert_eq!("64".parse(), Ok(Exposure::MeanHits(64.0)));
+        assert_eq!("median:8".parse(), Ok(Exposure::MedianHits(8.0)));
+        assert!("mode:8".parse::<Exposure>().is_err());
+        assert!("mean:-1".parse::<Exposure>().is_err());
+    }
+}
@@ -0,0 +1,352 @@
+//! Raw accumulation histograms on disk.
+//! 
+//! Tone mapping is cheap and rendering is not, so a render can keep
+//! its counts and be re-tone-mapped any number of times later.
+//! The file is a short header followed by the [`Histogram`] in `postcard`,
+//! whose variable length integers keep the many small counts compact.
+
+use std::{fmt::Display, io::Write, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{scene::Scene, RenderSettings, Strategy};
+use crate::my_grid::dyn_grid::{DynGrid, PixelType};
+
+const MAGIC: &[u8; 8] = b"RFHIST\0\0";
+
+/// Bumped when the layout of [`Histogram`] changes after a release
+pub const HISTOGRAM_VERSION: u32 = 1;
+
+/// The counts of a render together with the scene that produced them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram
+{
+    pub scene: Scene,
+    pub grid: DynGrid,
+}
+
+#[derive(Debug)]
+pub enum HistogramError
+{
+    Io(std::io::Error),
+    Encoding(postcard::Error),
+    /// The file does not start with the histogram header
+    NotAHistogram,
+    UnsupportedVersion(u32),
+    /// The grid does not match the dimensions in its scene
+    Inconsistent(String),
+    /// Histograms of different images cannot be merged
+    Incompatible(String),
+    /// Two histograms hold the same points, so merging would count them twice
+    Duplicate(String),
+}
+
+impl Display for HistogramError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            HistogramError::Io(e) => write!(f, "histogram file: {e}"),
+            HistogramError::Encoding(e) => write!(f, "histogram encoding: {e}"),
+            HistogramError::NotAHistogram => write!(f, "not a histogram file"),
+            HistogramError::UnsupportedVersion(v) => 
+                write!(f, "unsupported histogram version {v}, this build reads version {HISTOGRAM_VERSION}"),
+            HistogramError::Inconsistent(e) => write!(f, "inconsistent histogram: {e}"),
+            HistogramError::Incompatible(e) => write!(f, "incompatible histograms: {e}"),
+            HistogramError::Duplicate(e) => write!(f, "duplicate histograms: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HistogramError {}
+
+impl From<std::io::Error> for HistogramError
+{
+    fn from(value: std::io::Error) -> Self {
+        HistogramError::Io(value)
+    }
+}
+
+impl From<postcard::Error> for HistogramError
+{
+    fn from(value: postcard::Error) -> Self {
+        HistogramError::Encoding(value)
+    }
+}
+
+impl Histogram
+{
+    pub fn new<G>(scene: Scene, grid: G) -> Self
+    where
+        G: Into<DynGrid>
+    {
+        Histogram { scene, grid: grid.into() }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, HistogramError>
+    {
+        Ok(frame(MAGIC, HISTOGRAM_VERSION, self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Histogram, HistogramError>
+    {
+        let (version, body) = unframe(MAGIC, bytes).ok_or(HistogramError::NotAHistogram)?;
+        if version != HISTOGRAM_VERSION
+        {
+            return Err(HistogramError::UnsupportedVersion(version));
+        }
+
+        let h: Histogram = postcard::from_bytes(body)?;
+        h.check()?;
+        Ok(h)
+    }
+
+    pub fn save<Q>(&self, path: Q) -> Result<(), HistogramError>
+    where
+        Q: AsRef<Path>
+    {
+        Ok(write_atomically(path.as_ref(), &self.to_bytes()?)?)
+    }
+
+    pub fn load<Q>(path: Q) -> Result<Histogram, HistogramError>
+    where
+        Q: AsRef<Path>
+    {
+        Histogram::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Add up histograms of the same image, e.g. rendered on several machines with different seeds.
+    /// 
+    /// They must agree on the size, viewport, transform, weights and markov chain, and no two may share
+    /// their seed, generator and strategy, which would plot the same points twice.
+    /// Exhaustive renders do not depend on the seed, so at most one can be merged.
+    /// Everything else, and the image settings, comes from the first one, 
+    /// except that the points are summed. The counts are stored in the widest
+    /// pixel type among the inputs, or a wider one if the sums need it,
+    /// and hits past `u64::MAX` are counted as clipped.
+    pub fn merge(histograms: &[Histogram]) -> Result<Histogram, HistogramError>
+    {
+        let (first, rest) = histograms.split_first().ok_or(HistogramError::Incompatible("nothing to merge".to_string()))?;
+        let a = &first.scene.render;
+
+        for (i, h) in rest.iter().enumerate()
+        {
+            let b = &h.scene.render;
+            let differs = 
+                if (a.rows, a.cols) != (b.rows, b.cols) { Some("size") }
+                else if a.viewport != b.viewport { Some("viewport") }
+                else if a.transform != b.transform { Some("transform") }
+                else if a.weights != b.weights { Some("weights") }
+                else if a.markov != b.markov { Some("markov chain") }
+                else { None };
+
+            if let Some(field) = differs
+            {
+                return Err(HistogramError::Incompatible(format!("histogram {} has a different {field} from the first", i + 2)));
+            }
+        }
+
+        for (i, h) in histograms.iter().enumerate()
+        {
+            if let Some(j) = histograms[..i].iter().position(|g| Self::same_points(&g.scene.render, &h.scene.render))
+            {
+                let r = &h.scene.render;
+                return Err(HistogramError::Duplicate(
+                    if r.strategy == Strategy::Exhaustive 
+                    { 
+                        format!("histograms {} and {} are both exhaustive renders", j + 1, i + 1) 
+                    }
+                    else
+                    {
+                        format!("histograms {} and {} share seed {}, rng {} and strategy {}", j + 1, i + 1, r.seed, r.rng, r.strategy)
+                    }
+                ));
+            }
+        }
+
+        let mut counts = first.grid.counts();
+        let mut clipped = histograms.iter().fold(0_u64, |n, h| n.saturating_add(h.grid.clipped()));
+        for h in rest
+        {
+            for (a, b) in counts.iter_mut().zip(h.grid.counts())
+            {
+                clipped = clipped.saturating_add(b.saturating_sub(u64::MAX - *a));
+                *a = a.saturating_add(b);
+            }
+        }
+
+        let widest = histograms.iter().map(|h| h.grid.pixel_type()).max().unwrap_or(PixelType::U8);
+        let pixel_type = widest.widened_for(counts.iter().copied().max().unwrap_or(0));
+
+        let mut scene = first.scene.clone();
+        // no limit if any render had none
+        scene.render.points = histograms.iter().try_fold(0_usize, |n, h| Some(n.saturating_add(h.scene.render.points?)));
+        let mut grid = DynGrid::from_counts(a.rows, a.cols, a.viewport, pixel_type, &counts);
+        grid.add_clipped(clipped);
+
+        Ok(Histogram { scene, grid })
+    }
+
+    /// Whether renders with these settings plot the same points
+    fn same_points(a: &RenderSettings, b: &RenderSettings) -> bool
+    {
+        a.strategy == b.strategy && (a.strategy == Strategy::Exhaustive || (a.seed, a.rng) == (b.seed, b.rng))
+    }
+
+    fn check(&self) -> Result<(), HistogramError>
+    {
+        let r = &self.scene.render;
+        if (self.grid.rows(), self.grid.cols()) != (r.rows, r.cols)
+        {
+            return Err(HistogramError::Inconsistent(format!(
+                "grid is {}x{} but its scene is {}x{}", 
+                self.grid.cols(), self.grid.rows(), r.cols, r.rows
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `magic`, then `version` as a little endian `u32`, then `value` in postcard
+pub(super) fn frame<T>(magic: &[u8; 8], version: u32, value: &T) -> Result<Vec<u8>, postcard::Error>
+where
+    T: Serialize
+{
+    let mut bytes = Vec::from(&magic[..]);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    postcard::to_io(value, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// The version and payload of a [`frame`]d file, `None` if it does not start with `magic`
+pub(super) fn unframe<'a>(magic: &[u8; 8], bytes: &'a [u8]) -> Option<(u32, &'a [u8])>
+{
+    let body = bytes.strip_prefix(&magic[..])?;
+    if body.len() < 4 { return None }
+
+    let (version, body) = body.split_at(4);
+    Some((u32::from_le_bytes(version.try_into().unwrap()), body))
+}
+
+/// Write to a temporary file next to `path` and rename it into place,
+/// so `path` always holds either the old contents or all of the new ones
+pub(super) fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()>
+{
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = std::path::PathBuf::from(tmp);
+
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{Histogram, HistogramError};
+    use crate::{
+        fractal::rng::RngKind,
+        my_grid::{dyn_grid::{DynGrid, PixelType}, MyGrid},
+        render::{scene::Scene, Strategy}
+    };
+
+    fn small() -> Histogram
+    {
+        let mut scene = Scene::default();
+        scene.render.rows = 32;
+        scene.render.cols = 48;
+        scene.render.points = Some(5_000);
+        scene.render.seed = 3;
+
+        let grid: MyGrid<u16> = scene.render.render().unwrap();
+        Histogram::new(scene, grid)
+    }
+
+    #[test]
+    fn round_trips_through_bytes()
+    {
+        let h = small();
+        let back = Histogram::from_bytes(&h.to_bytes().unwrap()).unwrap();
+        assert_eq!(back, h);
+        assert!(matches!(back.grid, DynGrid::U16(_)));
+    }
+
+    #[test]
+    fn rejects_foreign_and_truncated_files()
+    {
+        assert!(matches!(Histogram::from_bytes(b"PNG..."), Err(HistogramError::NotAHistogram)));
+
+        let bytes = small().to_bytes().unwrap();
+        assert!(Histogram::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+
+        let mut future = bytes.clone();
+        future[8] = 99;
+        assert!(matches!(Histogram::from_bytes(&future), Err(HistogramError::UnsupportedVersion(99))));
+    }
+
+    #[test]
+    fn rejects_grid_not_matching_scene()
+    {
+        let mut h = small();
+        h.scene.render.rows = 7;
+        assert!(matches!(Histogram::from_bytes(&h.to_bytes().unwrap()), Err(HistogramError::Inconsistent(_))));
+    }
+
+    #[test]
+    fn merge_sums_counts_and_widens()
+    {
+        let a = small();
+        let mut b = small();
+        b.scene.render.seed = 4;
+        b.grid = b.scene.render.render::<u8>().unwrap().into();
+
+        let m = Histogram::merge(&[a.clone(), b.clone()]).unwrap();
+        assert_eq!(m.scene.render.points, Some(10_000));
+        assert!(matches!(m.grid, DynGrid::U16(_)));
+
+        let sums: Vec<u64> = a.grid.counts().iter().zip(b.grid.counts()).map(|(x, y)| x + y).collect();
+        assert_eq!(m.grid.counts(), sums);
+
+        let full = DynGrid::from_counts(32, 48, a.scene.render.viewport, PixelType::U16, &[u16::MAX.into(); 32 * 48]);
+        let full = Histogram::new(b.scene.clone(), full);
+        assert!(matches!(Histogram::merge(&[full, a.clone()]).unwrap().grid, DynGrid::U32(_)));
+
+        b.scene.render.transform.rot += 0.1;
+        assert!(matches!(Histogram::merge(&[a, b]), Err(HistogramError::Incompatible(_))));
+    }
+
+    #[test]
+    fn merge_rejects_the_same_points_twice()
+    {
+        let a = small();
+        assert!(matches!(Histogram::merge(&[a.clone(), a.clone()]), Err(HistogramError::Duplicate(_))));
+
+        let (mut b, mut c) = (a.clone(), a.clone());
+        (b.scene.render.seed, c.scene.render.seed) = (4, 5);
+        (b.scene.render.strategy, c.scene.render.strategy) = (Strategy::Exhaustive, Strategy::Exhaustive);
+        assert!(matches!(Histogram::merge(&[b, a.clone(), c]), Err(HistogramError::Duplicate(_))));
+
+        let mut d = a.clone();
+        d.scene.render.rng = RngKind::Pcg;
+        assert!(Histogram::merge(&[a, d]).is_ok());
+    }
+
+    #[test]
+    fn merge_counts_sums_past_u64_as_clipped()
+    {
+        let a = small();
+        let mut b = small();
+        b.scene.render.seed = 4;
+
+        let full = DynGrid::from_counts(32, 48, a.scene.render.viewport, PixelType::U64, &[u64::MAX; 32 * 48]);
+        let full = Histogram::new(b.scene, full);
+
+        let m = Histogram::merge(&[full, a.clone()]).unwrap();
+        assert!(m.grid.counts().iter().all(|&c| c == u64::MAX));
+        assert_eq!(m.grid.clipped(), a.grid.counts().iter().sum::<u64>());
+    }
+}
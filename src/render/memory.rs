@@ -0,0 +1,223 @@
+//! How much memory a render needs, checked against a budget before anything is allocated,
+//! so a mistyped size fails with an error instead of getting the process killed.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::{scene::Scene, tone::Palette, Strategy};
+use crate::my_grid::AllocError;
+
+/// Budget used when none is given
+pub const DEFAULT_BUDGET: u64 = 8 << 30;
+
+/// Bytes a render holds at its peak, by what they are for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MemoryEstimate
+{
+    pub pixels: u64,
+    /// The counts
+    pub grid: u64,
+    /// The grids the threads of [`Strategy::Parallel`] plot into before they are added up
+    pub thread_buffers: u64,
+    /// The two densities compared by convergence checks, see [`MemoryEstimate::with_convergence`]
+    pub convergence: u64,
+    /// The tone mapped image
+    pub image: u64,
+    /// What measuring the noise adds, see [`MemoryEstimate::with_noise`]
+    pub noise: u64,
+}
+
+impl MemoryEstimate
+{
+    /// For rendering `scene` into pixels of `pixel_size` bytes
+    pub fn of(scene: &Scene, pixel_size: usize) -> Result<Self, MemoryError>
+    {
+        let r = &scene.render;
+        let too_large = || MemoryError::TooLarge { rows: r.rows, cols: r.cols };
+
+        let pixels = (r.rows as u64).checked_mul(r.cols as u64).ok_or_else(too_large)?;
+        let grid = pixels.checked_mul(pixel_size as u64).ok_or_else(too_large)?;
+
+        let thread_buffers = match r.strategy
+        {
+            Strategy::Parallel => grid.checked_mul(r.threads as u64).ok_or_else(too_large)?,
+            _ => 0,
+        };
+
+        let channels = if scene.image.palette == Palette::Grey { 1 } else { 3 };
+        let image = pixels.checked_mul(channels).ok_or_else(too_large)?;
+
+        Ok(MemoryEstimate { pixels, grid, thread_buffers, convergence: 0, image, noise: 0 })
+    }
+
+    /// For moving a render of `scene` from pixels of `from` bytes to pixels of `to` bytes,
+    /// when both grids are held at once
+    pub fn of_widening(scene: &Scene, from: usize, to: usize) -> Result<Self, MemoryError>
+    {
+        let narrow = MemoryEstimate::of(scene, from)?;
+        let wide = MemoryEstimate::of(scene, to)?;
+        Ok(MemoryEstimate { grid: wide.grid.saturating_add(narrow.grid), ..wide })
+    }
+
+    /// Also counting the snapshots taken when stopping on convergence
+    pub fn with_convergence(self) -> Self
+    {
+        let per_pixel = 2 * std::mem::size_of::<f64>() as u64;
+        MemoryEstimate { convergence: self.pixels.saturating_mul(per_pixel), ..self }
+    }
+
+    /// Also counting what [`RenderSettings::render_with_noise`](super::RenderSettings::render_with_noise) 
+    /// holds besides the sum of the batches: the grid of the batch being rendered, as they are 
+    /// rendered one at a time, the mean and variance of every pixel, the relative errors and their map
+    pub fn with_noise(self) -> Self
+    {
+        let per_pixel = 3 * std::mem::size_of::<f64>() as u64 + 1;
+        MemoryEstimate { noise: self.grid.saturating_add(self.pixels.saturating_mul(per_pixel)), ..self }
+    }
+
+    pub fn total(&self) -> u64
+    {
+        [self.grid, self.thread_buffers, self.convergence, self.image, self.noise].into_iter().fold(0, u64::saturating_add)
+    }
+
+    /// The estimate itself, or an error if it is over `budget` bytes
+    pub fn check(self, budget: u64) -> Result<Self, MemoryError>
+    {
+        if self.total() > budget { Err(MemoryError::OverBudget { estimate: self, budget }) } else { Ok(self) }
+    }
+}
+
+impl Display for MemoryEstimate
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (grid {}", format_bytes(self.total()), format_bytes(self.grid))?;
+        if self.thread_buffers > 0 { write!(f, ", thread buffers {}", format_bytes(self.thread_buffers))? }
+        if self.convergence > 0 { write!(f, ", convergence {}", format_bytes(self.convergence))? }
+        if self.noise > 0 { write!(f, ", noise {}", format_bytes(self.noise))? }
+        write!(f, ", image {})", format_bytes(self.image))
+    }
+}
+
+/// Why a render could not get its memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError
+{
+    /// So many pixels that counting their bytes overflows
+    TooLarge { rows: usize, cols: usize },
+    OverBudget { estimate: MemoryEstimate, budget: u64 },
+    /// Within budget, but the allocator turned it down
+    Alloc(AllocError),
+}
+
+impl Display for MemoryError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            MemoryError::TooLarge { rows, cols } => write!(f, "a {cols}x{rows} render is too large to allocate"),
+            MemoryError::OverBudget { estimate, budget } =>
+                write!(f, "the render needs about {estimate}, over the memory budget of {}", format_bytes(*budget)),
+            MemoryError::Alloc(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+impl From<AllocError> for MemoryError
+{
+    fn from(value: AllocError) -> Self {
+        MemoryError::Alloc(value)
+    }
+}
+
+/// `bytes` in the largest binary unit that keeps it at 1 or more, e.g. `1.50 GiB`
+pub fn format_bytes(bytes: u64) -> String
+{
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len()
+    {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 { format!("{bytes} B") } else { format!("{value:.2} {}", UNITS[unit]) }
+}
+
+/// A number of bytes with an optional binary suffix, `K`, `M`, `G` or `T`, e.g. `512M` or `16GiB`
+pub fn parse_bytes(s: &str) -> Result<u64, String>
+{
+    let t = s.trim();
+    let digits = t.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(t.len());
+    let (number, suffix) = t.split_at(digits);
+
+    let shift = match suffix.trim().to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B')
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("unknown unit in '{s}', expected K, M, G or T")),
+    };
+
+    let value = number.parse::<f64>().map_err(|e| format!("invalid size '{s}': {e}"))? * (1_u64 << shift) as f64;
+    if !(value.is_finite() && value >= 0.0 && value < u64::MAX as f64) { return Err(format!("invalid size '{s}'")) }
+
+    Ok(value as u64)
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{format_bytes, parse_bytes, MemoryError, MemoryEstimate};
+    use crate::{error::FractalError, render::{scene::Scene, session::Session, tone::Palette, Strategy}};
+
+    #[test]
+    fn estimate_counts_every_buffer()
+    {
+        let mut scene = Scene::default();
+        (scene.render.rows, scene.render.cols, scene.render.strategy, scene.render.threads) = (100, 200, Strategy::Parallel, 4);
+        scene.image.palette = Palette::Fire;
+
+        let e = MemoryEstimate::of(&scene, 4).unwrap();
+        assert_eq!((e.grid, e.thread_buffers, e.image), (80_000, 320_000, 60_000));
+        assert_eq!(e.with_convergence().total(), 80_000 + 320_000 + 60_000 + 320_000);
+
+        assert!(matches!(e.check(400_000), Err(MemoryError::OverBudget { .. })));
+        assert_eq!(e.check(460_000), Ok(e));
+
+        let w = MemoryEstimate::of_widening(&scene, 4, 8).unwrap();
+        assert_eq!((w.grid, w.thread_buffers), (80_000 + 160_000, 640_000));
+
+        assert_eq!(e.with_noise().total(), e.total() + 80_000 + 20_000 * 25);
+    }
+
+    #[test]
+    fn huge_grids_fail_instead_of_aborting()
+    {
+        let mut scene = Scene::default();
+        (scene.render.rows, scene.render.cols) = (100_000, 100_000);
+        let over = Session::<u64>::try_new(scene.clone(), super::DEFAULT_BUDGET);
+        assert!(matches!(over, Err(FractalError::Memory(MemoryError::OverBudget { .. }))));
+
+        (scene.render.rows, scene.render.cols) = (usize::MAX, 2);
+        assert!(matches!(Session::<u8>::try_new(scene, u64::MAX), Err(FractalError::InvalidDimensions { .. })));
+    }
+
+    #[test]
+    fn bytes_parse_and_format()
+    {
+        assert_eq!(parse_bytes("512"), Ok(512));
+        assert_eq!(parse_bytes("16G"), Ok(16 << 30));
+        assert_eq!(parse_bytes("1.5 MiB"), Ok(3 << 19));
+        assert!(parse_bytes("3 parsecs").is_err());
+
+        assert_eq!(format_bytes(1000), "1000 B");
+        assert_eq!(format_bytes(3 << 29), "1.50 GiB");
+    }
+}
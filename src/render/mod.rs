@@ -0,0 +1,367 @@
+pub mod cancel;
+pub mod checkpoint;
+pub mod convergence;
+pub mod dyn_session;
+pub mod exposure;
+pub mod histogram;
+pub mod memory;
+pub mod noise;
+pub mod progress;
+pub mod scene;
+pub mod session;
+pub mod stats;
+pub mod tone;
+
+use std::{fmt::Display, str::FromStr, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::FractalError,
+    fractal::{markov::{self, ChoiceSource, TransitionMatrix, Weights}, rng::RngKind, Transform}, 
+    my_grid::{address::AddressTree, MyGrid, Viewport}
+};
+
+use {cancel::CancelToken, scene::Scene, session::{Outcome, Session}};
+
+/// Which backend fills the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy
+{
+    /// One long orbit, see [`MyGrid::walk`]
+    #[default]
+    Serial,
+    /// Many interleaved orbits, see [`MyGrid::fractalize_ensemble`]
+    Ensemble,
+    /// One orbit per thread, see [`MyGrid::fractalize_parallel`]
+    Parallel,
+    /// Every transform word, see [`MyGrid::fractalize_exhaustive`].
+    /// The depth is the smallest giving at least the requested number of points, and every leaf counts as one.
+    Exhaustive,
+}
+
+impl Strategy
+{
+    pub const ALL: [Strategy; 4] = [Strategy::Serial, Strategy::Ensemble, Strategy::Parallel, Strategy::Exhaustive];
+}
+
+impl Display for Strategy
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self
+        {
+            Strategy::Serial => "serial",
+            Strategy::Ensemble => "ensemble",
+            Strategy::Parallel => "parallel",
+            Strategy::Exhaustive => "exhaustive",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Strategy
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL
+        .into_iter()
+        .find(|k| k.to_string() == s.to_ascii_lowercase())
+        .ok_or_else(|| format!("unknown strategy '{s}', expected one of serial, ensemble, parallel, exhaustive"))
+    }
+}
+
+/// Everything needed to fill a grid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings
+{
+    #[serde(rename = "height")]
+    pub rows: usize,
+    #[serde(rename = "width")]
+    pub cols: usize,
+    /// No limit if `None`, for renders stopped by time, convergence or cancelling instead
+    #[serde(with = "scene::point_limit")]
+    pub points: Option<usize>,
+    pub transform: Transform,
+    /// Not supported by [`Strategy::Exhaustive`], which weighs every word equally
+    pub weights: Weights,
+    /// Pick each transform by the row of this matrix for the one before, instead of by the weights.
+    /// Not supported by [`Strategy::Exhaustive`] either.
+    pub markov: Option<TransitionMatrix>,
+    pub viewport: Viewport,
+    #[serde(with = "scene::large_int")]
+    pub seed: u64,
+    pub rng: RngKind,
+    pub strategy: Strategy,
+    /// Only used by [`Strategy::Parallel`]
+    pub threads: usize,
+}
+
+impl Default for RenderSettings
+{
+    fn default() -> Self 
+    {
+        RenderSettings 
+        { 
+            rows: 1024, 
+            cols: 1024, 
+            points: Some(10_000_000), 
+            transform: Transform::default(), 
+            weights: Weights::default(),
+            markov: None,
+            viewport: Viewport::default(),
+            seed: 0, 
+            rng: RngKind::default(), 
+            strategy: Strategy::default(), 
+            threads: 4,
+        }
+    }
+}
+
+impl RenderSettings
+{
+    /// Check every field, failing on the first bad one with what is wrong with it.
+    /// An empty grid is [`FractalError::InvalidDimensions`], anything else [`FractalError::Invalid`].
+    pub fn validate(&self) -> Result<(), FractalError>
+    {
+        if self.rows == 0 || self.cols == 0 { return Err(FractalError::InvalidDimensions { rows: self.rows, cols: self.cols }) }
+        if self.threads == 0 { return Err(FractalError::invalid("threads", "must be positive")) }
+
+        if !self.transform.rot.is_finite() { return Err(FractalError::invalid("transform.rot", "must be finite")) }
+        if !self.transform.theta_offset.is_finite() { return Err(FractalError::invalid("transform.theta_offset", "must be finite")) }
+
+        self.weights.validate().map_err(|e| FractalError::invalid("weights", e))?;
+        if self.strategy == Strategy::Exhaustive && !self.weights.is_uniform()
+        {
+            return Err(FractalError::invalid("weights", "the exhaustive strategy only supports equal weights"));
+        }
+        if let Some(matrix) = &self.markov
+        {
+            markov::validate(matrix).map_err(|e| FractalError::invalid("markov", e))?;
+            if self.weights != Weights::default()
+            {
+                return Err(FractalError::invalid("weights", "cannot be combined with a markov chain, which sets its own"));
+            }
+            if self.strategy == Strategy::Exhaustive
+            {
+                return Err(FractalError::invalid("markov", "the exhaustive strategy only supports equal weights"));
+            }
+        }
+        if self.strategy == Strategy::Exhaustive && self.points.is_none()
+        {
+            // the depth of the tree comes from the points, so there is no tree without a limit on them
+            return Err(FractalError::invalid("points", "must be limited for the exhaustive strategy"));
+        }
+
+        self.viewport.validate().map_err(|e| FractalError::invalid("viewport", e))?;
+
+        Ok(())
+    }
+
+    /// [`RenderSettings::points`], or `usize::MAX` without a limit
+    pub fn limit(&self) -> usize
+    {
+        self.points.unwrap_or(usize::MAX)
+    }
+
+    /// Where the transform choices come from, the Markov chain if there is one
+    pub fn choices(&self) -> ChoiceSource
+    {
+        match self.markov
+        {
+            Some(matrix) => ChoiceSource::Markov(matrix),
+            None => ChoiceSource::Weights(self.weights),
+        }
+    }
+
+    /// The tree [`Strategy::Exhaustive`] walks, the shallowest with at least [`RenderSettings::limit`] leaves
+    pub fn tree(&self) -> AddressTree
+    {
+        let depth = self.limit().max(1).checked_next_power_of_two().map_or(usize::BITS, |p| p.trailing_zeros()) as usize;
+        AddressTree { depth, ..Default::default() }
+    }
+
+    /// An empty grid of the configured size and viewport
+    pub fn grid<P>(&self) -> Result<MyGrid<P>, FractalError>
+    where
+        P: image::Primitive + Default
+    {
+        Ok(MyGrid::try_new(self.rows, self.cols)?.with_viewport(self.viewport))
+    }
+
+    /// Fill `grid` according to the settings, once they validate and limit the points.
+    /// The dimensions and viewport of `grid` are used as they are.
+    pub fn render_into<P>(&self, grid: &mut MyGrid<P>) -> Result<(), FractalError>
+    where
+        P: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        self.validate()?;
+        if self.points.is_none() { return Err(FractalError::invalid("points", "must be limited to render every point")) }
+
+        let empty = MyGrid::new(0, 0);
+        let scene = Scene { render: self.clone(), ..Default::default() };
+
+        let mut session = Session::with_grid(scene, std::mem::replace(grid, empty));
+        session.finish();
+        *grid = session.into_grid();
+        Ok(())
+    }
+
+    /// A fresh grid of the configured size, filled according to the settings
+    /// until every point is plotted or `cancel` is cancelled. 
+    /// The outcome is [`Outcome::Cancelled`] if it stopped early, with what was plotted up to then.
+    pub fn render_cancellable<P>(&self, cancel: &CancelToken) -> Result<Rendered<P>, FractalError>
+    where
+        P: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        self.validate()?;
+        let mut session = Session::with_grid(Scene { render: self.clone(), ..Default::default() }, self.grid()?);
+
+        while !session.is_done() && !cancel.is_cancelled()
+        {
+            session.advance_until(usize::MAX, cancel);
+        }
+
+        Ok(Rendered::of(session, Outcome::Cancelled))
+    }
+
+    /// A fresh grid of the configured size, filled according to the settings
+    /// for as long as `budget` allows, with `points` only an upper limit if any.
+    /// The outcome is [`Outcome::OutOfTime`] if it stopped early, with what was plotted up to then.
+    pub fn render_for<P>(&self, budget: Duration) -> Result<Rendered<P>, FractalError>
+    where
+        P: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        self.validate()?;
+        let mut session = Session::with_grid(Scene { render: self.clone(), ..Default::default() }, self.grid()?);
+        let deadline = Instant::now().checked_add(budget);
+        let in_time = || deadline.is_none_or(|d| Instant::now() < d);
+
+        while !session.is_done() && in_time()
+        {
+            session.advance_while(usize::MAX, &in_time);
+        }
+
+        Ok(Rendered::of(session, Outcome::OutOfTime))
+    }
+
+    /// A fresh grid of the configured size, filled according to the settings
+    pub fn render<P>(&self) -> Result<MyGrid<P>, FractalError>
+    where
+        P: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        let mut grid = self.grid()?;
+        self.render_into(&mut grid)?;
+        Ok(grid)
+    }
+}
+
+/// A render that may have stopped before plotting every point
+#[derive(Debug)]
+pub struct Rendered<P>
+{
+    pub grid: MyGrid<P>,
+    /// Points plotted into `grid`
+    pub points: usize,
+    /// [`Outcome::Completed`], or why it stopped early
+    pub outcome: Outcome,
+}
+
+impl<P> Rendered<P>
+where
+    P: image::Primitive + Default + num_traits::CheckedAdd + Send
+{
+    /// What `session` got done, `early` being why if it is not done
+    fn of(session: Session<P>, early: Outcome) -> Self
+    {
+        let outcome = if session.is_done() { Outcome::Completed } else { early };
+        let points = session.points_done();
+        Rendered { grid: session.into_grid(), points, outcome }
+    }
+}
+
+/// A seed for when the caller did not pick one
+pub fn random_seed() -> u64
+{
+    rand::random()
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{cancel::CancelToken, session::Outcome, RenderSettings, Strategy};
+    use crate::{error::FractalError, fractal::markov::Weights};
+
+    #[test]
+    fn seeded_renders_are_reproducible()
+    {
+        for strategy in Strategy::ALL
+        {
+            let settings = RenderSettings { rows: 64, cols: 64, points: Some(20_000), seed: 5, strategy, ..Default::default() };
+            let a = settings.render::<u32>().unwrap();
+            let b = settings.render::<u32>().unwrap();
+            assert_eq!(a.as_slice(), b.as_slice(), "{strategy}");
+        }
+    }
+
+    #[test]
+    fn render_for_keeps_to_its_budget()
+    {
+        let settings = RenderSettings { rows: 64, cols: 64, points: None, seed: 5, ..Default::default() };
+
+        let start = std::time::Instant::now();
+        let rendered = settings.render_for::<u64>(std::time::Duration::from_millis(50)).unwrap();
+        assert!(start.elapsed().as_millis() < 1000);
+
+        assert_eq!(rendered.outcome, Outcome::OutOfTime);
+        assert!(rendered.points > 0);
+        assert_eq!(rendered.grid.as_slice().iter().sum::<u64>(), rendered.points as u64);
+    }
+
+    #[test]
+    fn cancelled_renders_say_so()
+    {
+        let settings = RenderSettings { rows: 64, cols: 64, points: Some(20_000), seed: 5, ..Default::default() };
+
+        let cancel = CancelToken::new();
+        let whole = settings.render_cancellable::<u32>(&cancel).unwrap();
+        assert_eq!((whole.outcome, whole.points), (Outcome::Completed, 20_000));
+
+        cancel.cancel();
+        let none = settings.render_cancellable::<u32>(&cancel).unwrap();
+        assert_eq!((none.outcome, none.points), (Outcome::Cancelled, 0));
+    }
+
+    #[test]
+    fn bad_settings_fail_to_render()
+    {
+        let empty = RenderSettings { rows: 0, ..Default::default() };
+        assert!(matches!(empty.render::<u8>(), Err(FractalError::InvalidDimensions { rows: 0, cols: 1024 })));
+
+        let threadless = RenderSettings { rows: 8, cols: 8, threads: 0, ..Default::default() };
+        assert!(matches!(threadless.render::<u8>(), Err(FractalError::Invalid { field, .. }) if field == "threads"));
+
+        let endless = RenderSettings { rows: 8, cols: 8, points: None, strategy: Strategy::Exhaustive, ..Default::default() };
+        assert!(matches!(endless.render::<u8>(), Err(FractalError::Invalid { field, .. }) if field == "points"));
+
+        let chained = RenderSettings { rows: 8, cols: 8, points: Some(100), markov: Some([[0.5, 0.6], [0.5, 0.5]]), ..Default::default() };
+        assert!(matches!(chained.render::<u8>(), Err(FractalError::Invalid { field, .. }) if field == "markov"));
+        let chained = RenderSettings { markov: Some([[0.0, 1.0], [1.0, 0.0]]), ..chained };
+        assert!(chained.render::<u8>().is_ok());
+        let weighted = RenderSettings { weights: Weights([1.0, 3.0]), ..chained.clone() };
+        assert!(matches!(weighted.render::<u8>(), Err(FractalError::Invalid { field, .. }) if field == "weights"));
+        let exhaustive = RenderSettings { strategy: Strategy::Exhaustive, ..chained };
+        assert!(matches!(exhaustive.render::<u8>(), Err(FractalError::Invalid { field, .. }) if field == "markov"));
+    }
+
+    #[test]
+    fn strategy_round_trips_through_str()
+    {
+        for s in Strategy::ALL
+        {
+            assert_eq!(s.to_string().parse::<Strategy>(), Ok(s));
+        }
+    }
+}
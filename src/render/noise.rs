@@ -0,0 +1,220 @@
+//! Where an image is still noisy.
+//!
+//! The points are split into independently seeded batches. How much the
+//! count of a pixel varies between batches estimates the noise left in
+//! its total, and since that falls like one over the square root of the
+//! points, also how many points the pixel needs to get to a given noise.
+
+use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use rand::RngCore;
+
+use super::{cancel::CancelToken, session::Outcome, tone::{Palette, ToneMap}, RenderSettings, Rendered, Strategy};
+use crate::{error::FractalError, fractal::rng::SplitMix64};
+
+/// Spread of every pixel between the finished batches of a render, see [`RenderSettings::render_with_noise`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseMap
+{
+    rows: usize,
+    cols: usize,
+    batches: usize,
+    points: usize,
+    /// Mean count of each pixel over the batches
+    mean: Vec<f64>,
+    /// Sample variance of each pixel over the batches
+    variance: Vec<f64>,
+}
+
+impl NoiseMap
+{
+    pub fn rows(&self) -> usize
+    {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize
+    {
+        self.cols
+    }
+
+    pub fn batches(&self) -> usize
+    {
+        self.batches
+    }
+
+    /// Points plotted over the batches
+    pub fn points(&self) -> usize
+    {
+        self.points
+    }
+
+    pub fn mean(&self) -> &[f64]
+    {
+        &self.mean
+    }
+
+    pub fn variance(&self) -> &[f64]
+    {
+        &self.variance
+    }
+
+    /// Standard error of the total count of each pixel over its total count, between 0 and 1.
+    /// Zero for pixels no batch hit, which have nothing to estimate it from.
+    pub fn relative_error(&self) -> Vec<f64>
+    {
+        let k = self.batches as f64;
+        self.mean
+        .iter()
+        .zip(&self.variance)
+        .map(|(&m, &v)| if m > 0.0 { (v / k).sqrt() / m } else { 0.0 })
+        .collect()
+    }
+
+    /// Median relative error over the pixels some batch hit, zero if none was
+    pub fn median_relative_error(&self) -> f64
+    {
+        let mut covered: Vec<f64> = self.relative_error()
+        .into_iter()
+        .zip(&self.mean)
+        .filter(|&(_, &m)| m > 0.0)
+        .map(|(e, _)| e)
+        .collect();
+
+        if covered.is_empty() { return 0.0 }
+        let mid = covered.len() / 2;
+        *covered.select_nth_unstable_by(mid, f64::total_cmp).1
+    }
+
+    /// Points the whole render would need for each pixel to reach a relative error of `target`.
+    /// Zero for pixels no batch hit.
+    pub fn points_needed(&self, target: f64) -> Vec<f64>
+    {
+        let points = self.points as f64;
+        self.relative_error().into_iter().map(|e| points * (e / target).powi(2)).collect()
+    }
+
+    /// Color the relative errors, zero being the start of the palette and one the end
+    pub fn colorize(&self, tone: ToneMap, palette: Palette) -> DynamicImage
+    {
+        let errors = self.relative_error();
+        let (w, h) = (self.cols as u32, self.rows as u32);
+
+        let t = |x: u32, y: u32| tone.apply(errors[y as usize * self.cols + x as usize], 1.0);
+
+        match palette
+        {
+            Palette::Grey => DynamicImage::ImageLuma8(GrayImage::from_fn(w, h, |x, y| Luma([(t(x, y) * 255.0).round() as u8]))),
+            _ => DynamicImage::ImageRgb8(RgbImage::from_fn(w, h, |x, y| palette.color(t(x, y)))),
+        }
+    }
+}
+
+impl RenderSettings
+{
+    /// Render the points in `batches` batches, seeded with successive outputs of SplitMix64
+    /// started at the seed, and measure how the pixels vary between them, until every batch
+    /// is done or `cancel` is cancelled.
+    /// Returns the sum of the batches, which is a render of all the points, with the noise map
+    /// of the batches that finished, or none if fewer than two did.
+    /// Fails for fewer than two batches and for [`Strategy::Exhaustive`], which has no noise.
+    pub fn render_with_noise<P>(&self, batches: usize, cancel: &CancelToken) -> Result<(Rendered<P>, Option<NoiseMap>), FractalError>
+    where
+        P: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        if batches < 2
+        {
+            return Err(FractalError::invalid("batches", "must be at least 2 to measure the noise"));
+        }
+        if self.strategy == Strategy::Exhaustive
+        {
+            return Err(FractalError::invalid("strategy", "exhaustive has no noise to measure"));
+        }
+        let Some(points) = self.points else { return Err(FractalError::invalid("points", "must be limited to split into batches")) };
+
+        let mut total = Rendered { grid: self.grid::<P>()?, points: 0, outcome: Outcome::Completed };
+        let pixels = self.rows * self.cols;
+        let (mut mean, mut m2) = (vec![0.0; pixels], vec![0.0; pixels]);
+        let (mut done, mut done_points) = (0, 0);
+
+        let mut seeds = SplitMix64::new(self.seed);
+        for b in 0..batches
+        {
+            let batch = RenderSettings
+            {
+                points: Some(points / batches + usize::from(b < points % batches)),
+                seed: seeds.next_u64(),
+                ..self.clone()
+            };
+            let rendered = batch.render_cancellable::<P>(cancel)?;
+            total.grid.add_grid(&rendered.grid);
+            total.points += rendered.points;
+
+            // a partial batch counts towards the image but not the noise
+            if rendered.outcome == Outcome::Cancelled
+            {
+                total.outcome = Outcome::Cancelled;
+                break;
+            }
+
+            // Welford's running mean and sum of squared deviations
+            (done, done_points) = (done + 1, done_points + rendered.points);
+            let n = done as f64;
+            for ((p, m), s) in rendered.grid.as_slice().iter().zip(&mut mean).zip(&mut m2)
+            {
+                let c = p.to_f64().unwrap_or(0.0);
+                let d = c - *m;
+                *m += d / n;
+                *s += d * (c - *m);
+            }
+        }
+
+        let noise = (done >= 2).then(
+            ||
+            {
+                let variance = m2.into_iter().map(|s| s / (done - 1) as f64).collect();
+                NoiseMap { rows: self.rows, cols: self.cols, batches: done, points: done_points, mean, variance }
+            }
+        );
+
+        Ok((total, noise))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use crate::render::{cancel::CancelToken, session::Outcome, RenderSettings, Strategy};
+
+    #[test]
+    fn batches_add_up_and_noise_falls_with_points()
+    {
+        let settings = RenderSettings { rows: 64, cols: 64, points: Some(100_000), seed: 9, ..Default::default() };
+
+        let cancel = CancelToken::new();
+        let (rendered, noise) = settings.render_with_noise::<u32>(8, &cancel).unwrap();
+        let noise = noise.unwrap();
+        assert_eq!(rendered.outcome, Outcome::Completed);
+        assert_eq!(rendered.grid.as_slice().iter().map(|&c| c as usize).sum::<usize>(), settings.points.unwrap());
+        let total: f64 = noise.mean().iter().sum::<f64>() * 8.0;
+        assert!((total - settings.limit() as f64).abs() < 1e-6);
+
+        let errors = noise.relative_error();
+        assert!(errors.iter().all(|e| (0.0..=1.0 + 1e-9).contains(e)));
+
+        let more = RenderSettings { points: Some(1_600_000), ..settings.clone() };
+        let less_noise = more.render_with_noise::<u32>(8, &cancel).unwrap().1.unwrap();
+
+        // sixteen times the points, about a quarter of the noise
+        let ratio = less_noise.median_relative_error() / noise.median_relative_error();
+        assert!((0.15..0.4).contains(&ratio), "{ratio}");
+
+        assert!(settings.render_with_noise::<u32>(1, &cancel).is_err());
+        let exhaustive = RenderSettings { strategy: Strategy::Exhaustive, ..settings.clone() };
+        assert!(exhaustive.render_with_noise::<u32>(8, &cancel).is_err());
+
+        cancel.cancel();
+        let (rendered, noise) = settings.render_with_noise::<u32>(8, &cancel).unwrap();
+        assert_eq!((rendered.outcome, rendered.points), (Outcome::Cancelled, 0));
+        assert!(noise.is_none());
+    }
+}
@@ -0,0 +1,77 @@
+//! Feedback from long renders.
+
+use std::time::Duration;
+
+/// How far a render has got
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress
+{
+    pub points_done: usize,
+    /// `None` for a render without a limit on the points
+    pub points_total: Option<usize>,
+    /// Since this run started, not counting any earlier run it resumed
+    pub elapsed: Duration,
+    /// Points plotted per second by this run
+    pub points_per_sec: f64,
+    /// Relative change of the image at the latest convergence check, if the run checks
+    pub change: Option<f64>,
+}
+
+impl Progress
+{
+    /// Between 0 and 1, always 0 without a limit
+    pub fn fraction(&self) -> f64
+    {
+        match self.points_total
+        {
+            None => 0.0,
+            Some(0) => 1.0,
+            Some(total) => (self.points_done as f64 / total as f64).min(1.0),
+        }
+    }
+
+    /// Estimated time left at the current rate, `None` until there is a rate or without a limit
+    pub fn eta(&self) -> Option<Duration>
+    {
+        if self.points_per_sec.is_nan() || self.points_per_sec <= 0.0 { return None }
+
+        let left = self.points_total?.saturating_sub(self.points_done) as f64;
+        Duration::try_from_secs_f64(left / self.points_per_sec).ok()
+    }
+}
+
+/// Told how a render is going, see [`Session::run`](super::session::Session::run).
+/// Implemented by any `FnMut(&Progress)`.
+pub trait Observer
+{
+    fn progress(&mut self, progress: &Progress) -> ();
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&Progress)
+{
+    fn progress(&mut self, progress: &Progress) -> ()
+    {
+        self(progress)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::time::Duration;
+
+    use super::Progress;
+
+    #[test]
+    fn eta_follows_rate()
+    {
+        let p = Progress { points_done: 250, points_total: Some(1000), elapsed: Duration::from_secs(1), points_per_sec: 250.0, change: None };
+        assert_eq!(p.fraction(), 0.25);
+        assert_eq!(p.eta(), Some(Duration::from_secs(3)));
+
+        let start = Progress { points_per_sec: 0.0, ..p };
+        assert_eq!(start.eta(), None);
+    }
+}
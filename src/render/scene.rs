@@ -0,0 +1,481 @@
+//! Versioned scene files describing a render from start to finish.
+//! 
+//! A scene is TOML or JSON, picked by the file extension:
+//! 
+//! ```toml
+//! version = 1
+//! 
+//! [render]
+//! width = 1024
+//! height = 1024
+//! points = 10000000
+//! seed = 42
+//! rng = "xoshiro"
+//! strategy = "parallel"
+//! threads = 8
+//! weights = [1.0, 1.0]
+//! 
+//! [render.transform]
+//! rot = 1.724643921305295
+//! theta_offset = 3.0466792337230033
+//! 
+//! [render.viewport]
+//! x_min = -1.0
+//! x_max = 1.0
+//! y_min = -1.0
+//! y_max = 1.0
+//! 
+//! [image]
+//! tone = "gamma:2.2"
+//! palette = "fire"
+//! path = "fractal.png"
+//! ```
+//! 
+//! Everything but `version` may be left out and takes its default.
+//! Seeds and point counts past `i64::MAX`, which TOML cannot hold, are written as strings.
+//! `points = "unlimited"` leaves the render to be stopped by time or convergence instead.
+//! `markov = [[0.9, 0.1], [0.3, 0.7]]` picks each transform by the row of the one before
+//! instead of by `weights`, row and column `0` being the polar map and `1` the rotation.
+//! Errors name the offending field, e.g. `render.points`.
+
+use std::{fmt::Display, path::{Path, PathBuf}};
+
+use image::DynamicImage;
+use serde::{de::{self, Visitor}, Deserialize, Serialize};
+
+use super::{tone::{colorize_dyn, Palette, ToneMap}, RenderSettings};
+use crate::{error::FractalError, my_grid::dyn_grid::DynGrid};
+
+/// The only version this build reads and writes
+pub const SCENE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene
+{
+    pub version: u32,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub image: ImageSettings,
+}
+
+impl Default for Scene
+{
+    fn default() -> Self 
+    {
+        Scene 
+        { 
+            version: SCENE_VERSION, 
+            render: RenderSettings::default(), 
+            image: ImageSettings::default() 
+        }
+    }
+}
+
+/// How the finished grid becomes an image file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageSettings
+{
+    pub tone: ToneMap,
+    pub palette: Palette,
+    pub path: PathBuf,
+    /// Follows the extension of `path` when left out
+    pub format: Option<OutputFormat>,
+}
+
+impl Default for ImageSettings
+{
+    fn default() -> Self 
+    {
+        ImageSettings 
+        { 
+            tone: ToneMap::default(), 
+            palette: Palette::default(), 
+            path: PathBuf::from("fractal.png"), 
+            format: None 
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat
+{
+    Png,
+    Jpeg,
+    Bmp,
+    Tiff,
+}
+
+impl From<OutputFormat> for image::ImageFormat
+{
+    fn from(value: OutputFormat) -> Self {
+        match value
+        {
+            OutputFormat::Png => image::ImageFormat::Png,
+            OutputFormat::Jpeg => image::ImageFormat::Jpeg,
+            OutputFormat::Bmp => image::ImageFormat::Bmp,
+            OutputFormat::Tiff => image::ImageFormat::Tiff,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError
+{
+    Io(std::io::Error),
+    /// Malformed file, or a field of the wrong type or unknown name
+    Parse { field: String, message: String },
+    /// A well-formed field whose value cannot be rendered
+    Invalid { field: String, message: String },
+    UnsupportedVersion(u32),
+    /// A scene the format cannot hold
+    Serialize(String),
+    /// Neither `.toml` nor `.json`
+    UnknownExtension(PathBuf),
+}
+
+impl Display for SceneError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            SceneError::Io(e) => write!(f, "could not read scene: {e}"),
+            SceneError::Parse { field, message } => write!(f, "scene field `{field}`: {message}"),
+            SceneError::Invalid { field, message } => write!(f, "scene field `{field}` {message}"),
+            SceneError::Serialize(message) => write!(f, "could not write scene: {message}"),
+            SceneError::UnsupportedVersion(v) => 
+                write!(f, "scene field `version`: unsupported version {v}, this build reads version {SCENE_VERSION}"),
+            SceneError::UnknownExtension(p) => 
+                write!(f, "cannot tell the scene format of {}, expected a .toml or .json file", p.display()),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError
+{
+    fn from(value: std::io::Error) -> Self {
+        SceneError::Io(value)
+    }
+}
+
+/// Integers TOML cannot hold, being past `i64::MAX`, written as decimal strings.
+/// Either form is read back. Binary formats keep the plain integer.
+pub(super) mod large_int
+{
+    use serde::{de, Deserializer, Serializer};
+
+    use super::Count;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + TryInto<u64>,
+        <T as TryInto<u64>>::Error: std::fmt::Display,
+        S: Serializer
+    {
+        let n = (*value).try_into().map_err(serde::ser::Error::custom)?;
+        if serializer.is_human_readable() && i64::try_from(n).is_err()
+        {
+            serializer.serialize_str(&n.to_string())
+        }
+        else
+        {
+            serializer.serialize_u64(n)
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: TryFrom<u64>,
+        D: Deserializer<'de>
+    {
+        let count = Count { unlimited: false };
+        let n = if deserializer.is_human_readable() { deserializer.deserialize_any(count)? } else { deserializer.deserialize_u64(count)? };
+        let n = n.ok_or_else(|| de::Error::custom("a limit is required"))?;
+        T::try_from(n).map_err(|_| de::Error::custom(format!("{n} is out of range")))
+    }
+}
+
+/// [`large_int`] for an optional limit, written as `"unlimited"` when there is none
+pub(super) mod point_limit
+{
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Count, UNLIMITED};
+
+    pub fn serialize<S>(value: &Option<usize>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        match value
+        {
+            _ if !serializer.is_human_readable() => value.map(|n| n as u64).serialize(serializer),
+            Some(n) => super::large_int::serialize(n, serializer),
+            None => serializer.serialize_str(UNLIMITED),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let n = 
+            if deserializer.is_human_readable() { deserializer.deserialize_any(Count { unlimited: true })? } 
+            else { Option::<u64>::deserialize(deserializer)? };
+
+        n.map(|n| usize::try_from(n).map_err(|_| de::Error::custom(format!("{n} is out of range")))).transpose()
+    }
+}
+
+const UNLIMITED: &str = "unlimited";
+
+/// Reads a count from an integer or a string, and no limit from [`UNLIMITED`] if allowed
+struct Count
+{
+    unlimited: bool,
+}
+
+impl Visitor<'_> for Count
+{
+    type Value = Option<u64>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a non-negative integer, or one in a string")?;
+        if self.unlimited { write!(f, ", or \"{UNLIMITED}\"")? }
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Some(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        u64::try_from(v).map(Some).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        if self.unlimited && v == UNLIMITED { return Ok(None) }
+        v.parse().map(Some).map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+/// `serde_path_to_error` reports the root as `.`
+fn field_name(path: &serde_path_to_error::Path) -> String
+{
+    match path.to_string().as_str()
+    {
+        "." => "scene".to_string(),
+        p => p.to_string(),
+    }
+}
+
+enum Format
+{
+    Toml,
+    Json,
+}
+
+impl Format
+{
+    fn of(path: &Path) -> Result<Format, SceneError>
+    {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref()
+        {
+            Some("toml") => Ok(Format::Toml),
+            Some("json") => Ok(Format::Json),
+            _ => Err(SceneError::UnknownExtension(path.to_path_buf())),
+        }
+    }
+}
+
+impl Scene
+{
+    pub fn from_toml_str(text: &str) -> Result<Scene, SceneError>
+    {
+        let de = toml::Deserializer::new(text);
+        let scene: Scene = 
+            serde_path_to_error::deserialize(de)
+            .map_err(
+                |e|
+                {
+                    let line = e.inner().span().map(|s| text[..s.start].lines().count().max(1));
+                    let message = match line
+                    {
+                        Some(line) => format!("{} (line {line})", e.inner().message()),
+                        None => e.inner().message().to_string(),
+                    };
+                    SceneError::Parse { field: field_name(e.path()), message }
+                }
+            )?;
+
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Scene, SceneError>
+    {
+        let mut de = serde_json::Deserializer::from_str(text);
+        let scene: Scene = 
+            serde_path_to_error::deserialize(&mut de)
+            .map_err(|e| SceneError::Parse { field: field_name(e.path()), message: e.inner().to_string() })?;
+
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Read and validate a `.toml` or `.json` scene
+    pub fn load<Q>(path: Q) -> Result<Scene, SceneError>
+    where
+        Q: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let format = Format::of(path)?;
+        let text = std::fs::read_to_string(path)?;
+
+        match format
+        {
+            Format::Toml => Scene::from_toml_str(&text),
+            Format::Json => Scene::from_json_str(&text),
+        }
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, SceneError>
+    {
+        toml::to_string_pretty(self).map_err(|e| SceneError::Serialize(e.to_string()))
+    }
+
+    pub fn to_json_string(&self) -> Result<String, SceneError>
+    {
+        serde_json::to_string_pretty(self).map_err(|e| SceneError::Serialize(e.to_string()))
+    }
+
+    /// Write the scene as `.toml` or `.json`, following the extension of `path`
+    pub fn save<Q>(&self, path: Q) -> Result<(), SceneError>
+    where
+        Q: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let text = match Format::of(path)?
+        {
+            Format::Toml => self.to_toml_string()?,
+            Format::Json => self.to_json_string()?,
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), SceneError>
+    {
+        if self.version != SCENE_VERSION
+        {
+            return Err(SceneError::UnsupportedVersion(self.version));
+        }
+
+        self.render
+        .validate()
+        .map_err(
+            |e| match e
+            {
+                FractalError::InvalidDimensions { rows, .. } => 
+                {
+                    let field = if rows == 0 { "render.height" } else { "render.width" };
+                    SceneError::Invalid { field: field.to_string(), message: "must be positive".to_string() }
+                },
+                FractalError::Invalid { field, message } => SceneError::Invalid { field: format!("render.{field}"), message },
+                other => SceneError::Invalid { field: "render".to_string(), message: other.to_string() },
+            }
+        )
+    }
+
+    /// Tone map and color `grid` as described by [`Scene::image`]
+    pub fn colorize(&self, grid: &DynGrid) -> DynamicImage
+    {
+        colorize_dyn(grid, self.image.tone, self.image.palette)
+    }
+
+    /// Save an image colored by [`Scene::colorize`] to the path and in the format of [`Scene::image`]
+    pub fn write_image(&self, img: &DynamicImage) -> image::ImageResult<()>
+    {
+        match self.image.format
+        {
+            Some(format) => img.save_with_format(&self.image.path, format.into()),
+            None => img.save(&self.image.path),
+        }
+    }
+
+    /// Tone map and color `grid` and save it as described by [`Scene::image`]
+    pub fn save_image(&self, grid: &DynGrid) -> image::ImageResult<()>
+    {
+        self.write_image(&self.colorize(grid))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{Scene, SceneError};
+    use crate::render::{tone::{Palette, ToneMap}, Strategy};
+
+    #[test]
+    fn toml_and_json_round_trip()
+    {
+        let mut scene = Scene::default();
+        scene.render.points = Some(1234);
+        scene.render.strategy = Strategy::Ensemble;
+        scene.image.tone = ToneMap::Gamma(2.2);
+        scene.image.palette = Palette::Viridis;
+
+        assert_eq!(Scene::from_toml_str(&scene.to_toml_string().unwrap()).unwrap(), scene);
+        assert_eq!(Scene::from_json_str(&scene.to_json_string().unwrap()).unwrap(), scene);
+
+        scene.render.markov = Some([[0.9, 0.1], [0.3, 0.7]]);
+        assert_eq!(Scene::from_toml_str(&scene.to_toml_string().unwrap()).unwrap(), scene);
+        assert_eq!(Scene::from_json_str(&scene.to_json_string().unwrap()).unwrap(), scene);
+    }
+
+    #[test]
+    fn integers_past_i64_round_trip()
+    {
+        let mut scene = Scene::default();
+        (scene.render.seed, scene.render.points) = (u64::MAX, Some(usize::MAX / 2 + 7));
+
+        let toml = scene.to_toml_string().unwrap();
+        assert!(toml.contains(&format!("seed = \"{}\"", u64::MAX)), "{toml}");
+        assert_eq!(Scene::from_toml_str(&toml).unwrap(), scene);
+        assert_eq!(Scene::from_json_str(&scene.to_json_string().unwrap()).unwrap(), scene);
+
+        let small = Scene::from_toml_str("version = 1\n[render]\nseed = \"42\"\npoints = 9\n").unwrap();
+        assert_eq!((small.render.seed, small.render.points), (42, Some(9)));
+        assert!(Scene::from_toml_str("version = 1\n[render]\nseed = -1\n").is_err());
+    }
+
+    #[test]
+    fn missing_fields_take_defaults()
+    {
+        let scene = Scene::from_toml_str("version = 1\n[render]\npoints = 5\n").unwrap();
+        assert_eq!(scene.render.points, Some(5));
+        assert_eq!(scene.render.cols, Scene::default().render.cols);
+    }
+
+    #[test]
+    fn errors_name_the_field()
+    {
+        let field = |r: Result<Scene, SceneError>| match r
+        {
+            Err(SceneError::Parse { field, .. }) | Err(SceneError::Invalid { field, .. }) => field,
+            other => panic!("unexpected {other:?}"),
+        };
+
+        assert_eq!(field(Scene::from_toml_str("version = 1\n[render]\npoints = \"many\"\n")), "render.points");
+        assert_eq!(field(Scene::from_toml_str("version = 1\n[render.transform]\nspin = 2.0\n")), "render.transform.spin");
+        assert_eq!(field(Scene::from_json_str(r#"{"version": 1, "image": {"tone": "loud"}}"#)), "image.tone");
+        assert_eq!(field(Scene::from_toml_str("version = 1\n[render]\nwidth = 0\n")), "render.width");
+        assert_eq!(field(Scene::from_toml_str("version = 1\n[render]\nweights = [1.0, -2.0]\n")), "render.weights");
+
+        assert!(matches!(Scene::from_toml_str("version = 7\n"), Err(SceneError::UnsupportedVersion(7))));
+    }
+}
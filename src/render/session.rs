@@ -0,0 +1,886 @@
+//! Renders that advance a slice at a time.
+//!
+//! A [`Session`] holds everything a render needs between slices: the
+//! grid, where each orbit is and how far each choice stream has got.
+//! That is enough to stop after any slice, save a [`Checkpoint`] and
+//! later carry on with exactly the counts an uninterrupted render gets.
+
+use std::{path::PathBuf, thread, time::{Duration, Instant}};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cancel::CancelToken, checkpoint::{Checkpoint, CheckpointError}, convergence::{Convergence, Tracker}, 
+    memory::MemoryEstimate, progress::{Observer, Progress}, scene::Scene, Strategy
+};
+use crate::{
+    error::FractalError,
+    fractal::{markov::Choices, rng::SplitMix64},
+    my_grid::{address::AddressTree, dyn_grid::DynGrid, ensemble::Ensemble, AllocError, MyGrid}
+};
+
+/// Points each choice stream plots per slice of [`Session::run`]
+const SLICE: usize = 1 << 22;
+
+/// Points plotted between looks at the [`CancelToken`]
+const POLL: usize = 1 << 16;
+
+/// How far a render has got, apart from its counts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderState
+{
+    /// Current point of every orbit
+    pub walkers: Vec<(f64, f64)>,
+    /// The walker due next, only used by [`Strategy::Ensemble`]
+    pub next_walker: usize,
+    /// Choices drawn so far from each stream
+    pub used: Vec<usize>,
+    /// Parts of the tree rendered so far by [`Strategy::Exhaustive`]
+    pub parts_done: usize,
+    /// Leaves of the next part rendered so far by [`Strategy::Exhaustive`]
+    pub leaves_done: u128,
+    /// Policy of the latest [`Session::run`], for a resumed render to carry on with
+    pub overflow: Overflow,
+}
+
+/// What [`Session::run`] does besides plotting
+pub struct RunOptions<'a>
+{
+    /// Where to save a checkpoint every `checkpoint_every`
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_every: Duration,
+    /// Told about progress every `report_every`, and once more when the render is done
+    pub observer: Option<Box<dyn Observer + 'a>>,
+    pub report_every: Duration,
+    /// Stops the render, with every worker thread finishing within a few thousand points
+    pub cancel: Option<CancelToken>,
+    /// Stops the render once this much time has passed, the same way as `cancel`.
+    /// `points` in the scene is then only an upper limit.
+    /// An exhaustive render stops between leaves of its tree, leaving the rest of the image empty.
+    pub budget: Option<Duration>,
+    /// Stops the render once more points hardly change the image.
+    /// `points` in the scene is then only an upper limit.
+    /// Ignored by [`Strategy::Exhaustive`], which has no noise to wait out.
+    pub converge: Option<Convergence>,
+    pub overflow: Overflow,
+    /// Bytes [`DynSession::run`](super::dyn_session::DynSession::run) may use when it widens 
+    /// the grid, see [`MemoryEstimate::of_widening`]. Unchecked if `None`.
+    pub memory_budget: Option<u64>,
+}
+
+/// What [`Session::run`] does about hits that do not fit in their pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow
+{
+    /// Keep the pixel at the largest count it holds and count the hits as clipped
+    #[default]
+    Clip,
+    /// Stop the render after the slice in which a pixel first overflows, keeping the hits
+    /// that did not fit so [`Session::widen`] can add them back to a wider grid.
+    /// [`DynSession::run`](super::dyn_session::DynSession::run) then carries on by itself.
+    Widen,
+    /// Fail with [`FractalError::Overflow`] after the slice in which a pixel first overflows
+    Fail,
+}
+
+impl Overflow
+{
+    pub const ALL: [Overflow; 3] = [Overflow::Clip, Overflow::Widen, Overflow::Fail];
+}
+
+impl std::fmt::Display for Overflow
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self
+        {
+            Overflow::Clip => "clip",
+            Overflow::Widen => "widen",
+            Overflow::Fail => "fail",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::str::FromStr for Overflow
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Overflow::ALL
+        .into_iter()
+        .find(|k| k.to_string() == s.to_ascii_lowercase())
+        .ok_or_else(|| format!("unknown overflow policy '{s}', expected one of clip, widen, fail"))
+    }
+}
+
+/// Why [`Session::run`] returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome
+{
+    Completed,
+    /// The grid holds what was plotted up to then, see [`Session::points_done`]
+    Cancelled,
+    /// The time budget ran out, see [`Session::points_done`] for how far it got
+    OutOfTime,
+    /// The image stopped changing, see [`Session::points_done`] for how many points that took
+    Converged,
+    /// A pixel overflowed with [`Overflow::Widen`] set
+    Overflowed,
+}
+
+impl Default for RunOptions<'_>
+{
+    fn default() -> Self 
+    {
+        RunOptions 
+        { 
+            checkpoint: None, 
+            checkpoint_every: Duration::from_secs(60), 
+            observer: None, 
+            report_every: Duration::from_secs(1),
+            cancel: None,
+            budget: None,
+            converge: None,
+            overflow: Overflow::Clip,
+            memory_budget: None,
+        }
+    }
+}
+
+struct Stream
+{
+    choices: Choices<Box<dyn RngCore + Send>>,
+    share: usize,
+    used: usize,
+}
+
+/// A render in progress
+pub struct Session<P>
+{
+    scene: Scene,
+    grid: MyGrid<P>,
+    walkers: Vec<(f64, f64)>,
+    next_walker: usize,
+    streams: Vec<Stream>,
+    tree: AddressTree,
+    parts_done: usize,
+    leaves_done: u128,
+    overflow: Overflow,
+    /// Grids the threads of [`Strategy::Parallel`] plot into, emptied into `grid` after each slice.
+    /// Made up front except by [`Session::with_grid`], whose are made on the first slice.
+    buffers: Vec<MyGrid<P>>,
+}
+
+impl<P> Session<P>
+where
+    P: image::Primitive + Default + num_traits::CheckedAdd + Send
+{
+    /// A render of `scene` starting from an empty grid
+    pub fn new(scene: Scene) -> Self
+    {
+        let grid = MyGrid::new(scene.render.rows, scene.render.cols).with_viewport(scene.render.viewport);
+        Session::with_grid(scene, grid)
+    }
+
+    /// [`Session::new`], failing if the settings do not validate, the render would need 
+    /// more than `budget` bytes, see [`MemoryEstimate`], or the grid cannot be allocated
+    pub fn try_new(scene: Scene, budget: u64) -> Result<Self, FractalError>
+    {
+        scene.render.validate()?;
+        MemoryEstimate::of(&scene, std::mem::size_of::<P>())?.check(budget)?;
+        Session::try_alloc(scene)
+    }
+
+    /// [`Session::try_new`] for a caller that has checked the [`MemoryEstimate`] itself
+    pub fn try_alloc(scene: Scene) -> Result<Self, FractalError>
+    {
+        scene.render.validate()?;
+
+        let grid = MyGrid::try_new(scene.render.rows, scene.render.cols)?.with_viewport(scene.render.viewport);
+        let mut session = Session::with_grid(scene, grid);
+        session.buffers = session.buffers_for(&session.grid)?;
+        Ok(session)
+    }
+
+    /// A render of `scene` adding to `grid`, whose dimensions and viewport are used as they are
+    pub fn with_grid(scene: Scene, grid: MyGrid<P>) -> Self
+    {
+        let r = &scene.render;
+        let source = r.choices();
+        let stream = |rng: Box<dyn RngCore + Send>, share: usize| -> Stream
+        {
+            Stream { choices: source.choices(rng, share), share, used: 0 }
+        };
+
+        let (walkers, streams) = match r.strategy
+        {
+            Strategy::Serial =>
+            (
+                vec![(0.5, 0.5)],
+                vec![stream(r.rng.seeded(r.seed), r.limit())]
+            ),
+            Strategy::Ensemble =>
+            {
+                let mut rng = r.rng.seeded(r.seed);
+                let walkers = Ensemble::default().start_walkers(&r.transform, &source, &mut rng);
+                (walkers, vec![stream(rng, r.limit())])
+            },
+            // the same streams as MyGrid::fractalize_parallel
+            Strategy::Parallel =>
+            {
+                let (threads, points) = (r.threads.max(1), r.limit());
+                let mut seeds = SplitMix64::new(r.seed);
+                let streams =
+                    (0..threads)
+                    .map(|i| stream(r.rng.seeded(seeds.next_u64()), points / threads + usize::from(i < points % threads)))
+                    .collect();
+                (vec![(0.5, 0.5); threads], streams)
+            },
+            Strategy::Exhaustive => (vec![], vec![]),
+        };
+
+        let tree = r.tree();
+
+        Session { scene, grid, walkers, next_walker: 0, streams, tree, parts_done: 0, leaves_done: 0, overflow: Overflow::default(), buffers: vec![] }
+    }
+
+    /// Pick a render up from a checkpoint, skipping every choice stream
+    /// past what it had already drawn
+    pub fn resume(checkpoint: Checkpoint) -> Result<Self, FractalError>
+    where
+        MyGrid<P>: TryFrom<DynGrid, Error = DynGrid>
+    {
+        let Checkpoint { scene, grid, state } = checkpoint;
+
+        let grid = MyGrid::<P>::try_from(grid).map_err(
+            |g| CheckpointError::Inconsistent(format!("the checkpoint holds {} counts", g.pixel_type()))
+        )?;
+        let mut session = Session::with_grid(scene, grid);
+
+        if state.walkers.len() != session.walkers.len() || state.used.len() != session.streams.len()
+        {
+            return Err(CheckpointError::Inconsistent(format!(
+                "{} orbits and {} streams do not fit a {} render",
+                state.walkers.len(), state.used.len(), session.scene.render.strategy
+            )).into());
+        }
+
+        for (stream, used) in session.streams.iter_mut().zip(state.used)
+        {
+            if used > stream.share
+            {
+                return Err(CheckpointError::Inconsistent(format!("a stream used {used} of {} choices", stream.share)).into());
+            }
+            if used > 0
+            {
+                stream.choices.nth(used - 1);
+            }
+            stream.used = used;
+        }
+
+        if state.parts_done > session.tree.parts()
+        {
+            return Err(CheckpointError::Inconsistent(format!("{} parts done of {}", state.parts_done, session.tree.parts())).into());
+        }
+        if state.leaves_done >= session.tree.part_leaves() || (state.leaves_done > 0 && state.parts_done == session.tree.parts())
+        {
+            return Err(CheckpointError::Inconsistent(format!("{} leaves done of a part of {}", state.leaves_done, session.tree.part_leaves())).into());
+        }
+
+        session.walkers = state.walkers;
+        session.next_walker = state.next_walker;
+        session.parts_done = state.parts_done;
+        session.leaves_done = state.leaves_done;
+        session.overflow = state.overflow;
+        session.buffers = session.buffers_for(&session.grid)?;
+
+        Ok(session)
+    }
+
+    pub fn scene(&self) -> &Scene
+    {
+        &self.scene
+    }
+
+    pub fn grid(&self) -> &MyGrid<P>
+    {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> MyGrid<P>
+    {
+        self.grid
+    }
+
+    /// What the latest [`Session::run`], or the one resumed from, did about overflow
+    pub fn overflow(&self) -> Overflow
+    {
+        self.overflow
+    }
+
+    pub fn state(&self) -> RenderState
+    {
+        RenderState
+        {
+            walkers: self.walkers.clone(),
+            next_walker: self.next_walker,
+            used: self.streams.iter().map(|s| s.used).collect(),
+            parts_done: self.parts_done,
+            leaves_done: self.leaves_done,
+            overflow: self.overflow,
+        }
+    }
+
+    /// Points plotted so far. An exhaustive render counts the leaves of its tree done,
+    /// up to `usize::MAX`.
+    pub fn points_done(&self) -> usize
+    {
+        match self.scene.render.strategy
+        {
+            Strategy::Exhaustive => 
+            {
+                let leaves = self.parts_done as u128 * self.tree.part_leaves() + self.leaves_done;
+                usize::try_from(leaves).unwrap_or(usize::MAX)
+            },
+            _ => self.streams.iter().map(|s| s.used).sum(),
+        }
+    }
+
+    /// Points the render plots in all, `None` without a limit.
+    /// An exhaustive render plots every leaf of its tree, which may be more than the scene asks for.
+    pub fn points_total(&self) -> Option<usize>
+    {
+        match self.scene.render.strategy
+        {
+            Strategy::Exhaustive => Some(usize::try_from(self.tree.leaves()).unwrap_or(usize::MAX)),
+            _ => self.scene.render.points,
+        }
+    }
+
+    pub fn is_done(&self) -> bool
+    {
+        match self.scene.render.strategy
+        {
+            Strategy::Exhaustive => self.parts_done == self.tree.parts(),
+            _ => self.points_done() >= self.scene.render.limit(),
+        }
+    }
+
+    /// Plot up to `max_points` more points, split evenly between the orbits.
+    /// An exhaustive render does at least one part of its tree.
+    /// Returns how many were plotted.
+    pub fn advance(&mut self, max_points: usize) -> usize
+    {
+        self.advance_until(max_points, &CancelToken::new())
+    }
+
+    /// [`Session::advance`], stopping early once `cancel` is cancelled
+    pub fn advance_until(&mut self, max_points: usize, cancel: &CancelToken) -> usize
+    {
+        self.advance_while(max_points, &|| !cancel.is_cancelled())
+    }
+
+    /// [`Session::advance`], stopping early once `go` turns false. 
+    /// Every worker thread calls it every few thousand points.
+    pub(super) fn advance_while(&mut self, max_points: usize, go: &(dyn Fn() -> bool + Sync)) -> usize
+    {
+        let r = &self.scene.render;
+
+        match r.strategy
+        {
+            Strategy::Serial =>
+            {
+                let (s, w, grid) = (&mut self.streams[0], &mut self.walkers[0], &mut self.grid);
+                polled(max_points.min(s.share - s.used), go, 
+                    |k|
+                    {
+                        *w = grid.walk(&r.transform, *w, k, &mut s.choices);
+                        s.used += k;
+                    }
+                )
+            },
+            Strategy::Ensemble =>
+            {
+                let (s, walkers, next, grid) = (&mut self.streams[0], &mut self.walkers, &mut self.next_walker, &mut self.grid);
+                polled(max_points.min(s.share - s.used), go, 
+                    |k|
+                    {
+                        *next = grid.walk_interleaved(&r.transform, walkers, *next, k, &mut s.choices);
+                        s.used += k;
+                    }
+                )
+            },
+            Strategy::Parallel =>
+            {
+                let per_stream = max_points.div_ceil(self.streams.len());
+                let (rows, cols, viewport) = (self.grid.rows(), self.grid.cols(), *self.grid.viewport());
+                let carrying = self.grid.is_carrying();
+                let transform = &r.transform;
+
+                while self.buffers.len() < self.streams.len()
+                {
+                    self.buffers.push(MyGrid::new(rows, cols).with_viewport(viewport));
+                }
+
+                let done: Vec<usize> =
+                thread::scope(
+                |scope|
+                {
+                    let handles: Vec<_> =
+                        self.streams.iter_mut()
+                        .zip(self.walkers.iter_mut())
+                        .zip(self.buffers.iter_mut())
+                        .map(
+                        |((s, w), local)|
+                        {
+                            scope.spawn(
+                            move ||
+                            {
+                                if carrying { local.start_carrying() }
+                                polled(per_stream.min(s.share - s.used), go, 
+                                    |k|
+                                    {
+                                        *w = local.walk(transform, *w, k, &mut s.choices);
+                                        s.used += k;
+                                    }
+                                )
+                            })
+                        })
+                        .collect();
+
+                    handles.into_iter().map(|h| h.join().expect("Some thread panicked")).collect()
+                });
+
+                for local in &mut self.buffers
+                {
+                    self.grid.drain_grid(local);
+                }
+                done.into_iter().sum()
+            },
+            Strategy::Exhaustive =>
+            {
+                let parts = self.tree.parts();
+                let per_part = usize::try_from(self.tree.part_leaves()).unwrap_or(usize::MAX);
+                let end = (self.parts_done + max_points.div_ceil(per_part).max(1)).min(parts);
+
+                let before = self.points_done();
+                while self.parts_done < end && go()
+                {
+                    self.leaves_done = self.grid.fractalize_exhaustive_part_while(&r.transform, &self.tree, self.parts_done, self.leaves_done, go);
+                    if self.leaves_done < self.tree.part_leaves() { break }
+                    (self.parts_done, self.leaves_done) = (self.parts_done + 1, 0);
+                }
+                self.points_done() - before
+            },
+        }
+    }
+
+    /// Plot every remaining point
+    pub fn finish(&mut self) -> ()
+    {
+        while !self.is_done()
+        {
+            self.advance(usize::MAX);
+        }
+    }
+
+    /// Points per [`Session::advance`] when running in slices
+    fn slice(&self) -> usize
+    {
+        match self.scene.render.strategy
+        {
+            // one part of the tree
+            Strategy::Exhaustive => usize::try_from(self.tree.part_leaves()).unwrap_or(usize::MAX),
+            _ => SLICE * self.streams.len(),
+        }
+    }
+
+    /// Plot every remaining point a slice at a time, checkpointing
+    /// and reporting progress in between as `options` asks, 
+    /// unless cancelled or out of time first.
+    /// Fails if a checkpoint cannot be saved, or on overflow with [`Overflow::Fail`].
+    pub fn run(&mut self, options: &mut RunOptions) -> Result<Outcome, FractalError>
+    where
+        MyGrid<P>: Into<DynGrid>
+    {
+        let RunOptions { checkpoint, checkpoint_every, observer, report_every, cancel, budget, converge, overflow, .. } = options;
+        let (checkpoint_every, report_every, budget, overflow) = (*checkpoint_every, *report_every, *budget, *overflow);
+        let cancel = cancel.clone().unwrap_or_default();
+        self.overflow = overflow;
+        if overflow == Overflow::Widen { self.grid.start_carrying() }
+        let clipped_before = self.grid.clipped();
+
+        let start = Instant::now();
+        let deadline = budget.and_then(|b| start.checked_add(b));
+        let out_of_time = || deadline.is_some_and(|d| Instant::now() >= d);
+        let start_points = self.points_done();
+        let (mut last_checkpoint, mut last_report) = (start, start);
+
+        let mut tracker = 
+            converge
+            .filter(|_| self.scene.render.strategy != Strategy::Exhaustive)
+            .map(|c| Tracker::new(c, start_points));
+
+        loop
+        {
+            if self.is_done() { return Ok(Outcome::Completed) }
+            if cancel.is_cancelled() { return Ok(Outcome::Cancelled) }
+            if out_of_time() { return Ok(Outcome::OutOfTime) }
+
+            // stop at the next snapshot
+            let slice = tracker.as_ref().map_or(self.slice(), |t| self.slice().min(t.until_next(self.points_done())));
+            self.advance_while(slice, &|| !cancel.is_cancelled() && !out_of_time());
+
+            let converged = tracker.as_mut().is_some_and(|t| t.check(&self.grid, self.points_done()));
+            let overflowed = overflow == Overflow::Widen && self.grid.has_carry();
+            let clipped = self.grid.clipped() - clipped_before;
+            let failed = overflow == Overflow::Fail && clipped > 0;
+            let now = Instant::now();
+            let stopping = self.is_done() || cancel.is_cancelled() || out_of_time() || converged || overflowed || failed;
+
+            if let Some(path) = &checkpoint
+            {
+                if now - last_checkpoint >= checkpoint_every && !stopping
+                {
+                    self.checkpoint().save(path)?;
+                    last_checkpoint = now;
+                }
+            }
+
+            if let Some(observer) = observer.as_deref_mut()
+            {
+                if now - last_report >= report_every || stopping
+                {
+                    observer.progress(&self.progress(start, start_points, tracker.as_ref().and_then(|t| t.change)));
+                    last_report = now;
+                }
+            }
+
+            if converged { return Ok(Outcome::Converged) }
+            if overflowed { return Ok(Outcome::Overflowed) }
+            if failed { return Err(FractalError::Overflow { points: self.points_done(), clipped }) }
+        }
+    }
+
+    /// The same render with pixels of type `Q`, see [`MyGrid::widen`].
+    /// Fails if the wider grid or its thread buffers cannot be allocated.
+    pub fn widen<Q>(mut self) -> Result<Session<Q>, FractalError>
+    where
+        Q: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        self.take_widened()
+    }
+
+    /// [`Session::widen`], allocating before anything moves so a failure leaves this session
+    /// as it was. On success this session is left with an empty grid.
+    pub(super) fn take_widened<Q>(&mut self) -> Result<Session<Q>, FractalError>
+    where
+        Q: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        // empty between slices, so they can go first to make room
+        self.buffers = vec![];
+
+        let grid = self.grid.try_widen::<Q>()?;
+        let buffers = self.buffers_for(&grid)?;
+
+        let empty = Session::with_grid(self.scene.clone(), MyGrid::new(0, 0));
+        let Session { scene, walkers, next_walker, streams, tree, parts_done, leaves_done, overflow, .. } = std::mem::replace(self, empty);
+        Ok(Session { scene, grid, walkers, next_walker, streams, tree, parts_done, leaves_done, overflow, buffers })
+    }
+
+    /// Empty thread buffers shaped like `grid` for [`Strategy::Parallel`], none for the other strategies
+    fn buffers_for<Q>(&self, grid: &MyGrid<Q>) -> Result<Vec<MyGrid<Q>>, AllocError>
+    where
+        Q: image::Primitive + Default
+    {
+        let threads = if self.scene.render.strategy == Strategy::Parallel { self.streams.len() } else { 0 };
+        let (rows, cols, viewport) = (grid.rows(), grid.cols(), *grid.viewport());
+
+        (0..threads).map(|_| Ok(MyGrid::try_new(rows, cols)?.with_viewport(viewport))).collect()
+    }
+
+    /// Progress of a run that started at `start` with `start_points` already plotted
+    fn progress(&self, start: Instant, start_points: usize, change: Option<f64>) -> Progress
+    {
+        let elapsed = start.elapsed();
+        let points_done = self.points_done();
+
+        Progress
+        {
+            points_done,
+            points_total: self.points_total(),
+            elapsed,
+            points_per_sec: (points_done - start_points) as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            change,
+        }
+    }
+
+    /// A snapshot of the render that [`Session::resume`] continues from
+    pub fn checkpoint(&self) -> Checkpoint
+    where
+        MyGrid<P>: Into<DynGrid>
+    {
+        Checkpoint
+        {
+            scene: self.scene.clone(),
+            grid: self.grid.clone().into(),
+            state: self.state()
+        }
+    }
+}
+
+/// Hand `step` the `n` points in chunks until they run out or `go` turns false.
+/// Returns how many were handed out.
+fn polled<F>(n: usize, go: &(dyn Fn() -> bool + Sync), mut step: F) -> usize
+where
+    F: FnMut(usize)
+{
+    let mut done = 0;
+    while done < n && go()
+    {
+        let k = POLL.min(n - done);
+        step(k);
+        done += k;
+    }
+    done
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::time::Duration;
+
+    use super::{Outcome, Overflow, RunOptions, Session, SLICE};
+    use crate::{
+        error::FractalError,
+        fractal::markov::Weights,
+        render::{cancel::CancelToken, checkpoint::Checkpoint, convergence::Convergence, progress::Progress, scene::Scene, Strategy}
+    };
+
+    fn scene(strategy: Strategy) -> Scene
+    {
+        let mut scene = Scene::default();
+        scene.render.rows = 48;
+        scene.render.cols = 64;
+        scene.render.points = Some(30_001);
+        scene.render.seed = 11;
+        scene.render.threads = 3;
+        scene.render.strategy = strategy;
+        scene
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_one()
+    {
+        let chain = Some([[0.2, 0.8], [0.6, 0.4]]);
+        for (weights, markov) in [(Weights::default(), None), (Weights([1.0, 3.0]), None), (Weights::default(), chain)]
+        {
+            for strategy in Strategy::ALL
+            {
+                let mut scene = scene(strategy);
+                if strategy != Strategy::Exhaustive { (scene.render.weights, scene.render.markov) = (weights, markov) }
+
+                let whole = scene.render.render::<u32>().unwrap();
+
+                let mut first = Session::<u32>::new(scene);
+                first.advance(12_345);
+                let bytes = first.checkpoint().to_bytes().unwrap();
+                drop(first);
+
+                let mut second = Session::<u32>::resume(Checkpoint::from_bytes(&bytes).unwrap()).unwrap();
+                second.advance(7);
+                second.finish();
+
+                assert_eq!(second.grid().as_slice(), whole.as_slice(), "{strategy} {weights:?} {markov:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn run_reports_progress_up_to_the_end()
+    {
+        for strategy in Strategy::ALL
+        {
+            let mut scene = scene(strategy);
+            let points = if strategy == Strategy::Exhaustive { 1 << 14 } else { 2 * SLICE + 1 };
+            (scene.render.points, scene.render.threads) = (Some(points), 1);
+
+            let mut seen = vec![];
+            let observer = |p: &Progress| seen.push(p.points_done);
+            let mut options = RunOptions { observer: Some(Box::new(observer)), report_every: Duration::ZERO, ..Default::default() };
+
+            let mut s = Session::<u32>::new(scene);
+            s.run(&mut options).unwrap();
+            // lets go of `seen`
+            drop(options);
+
+            assert!(seen.len() > 1, "{strategy}");
+            assert!(seen.windows(2).all(|w| w[0] < w[1]), "{strategy}");
+            assert_eq!(seen.last(), Some(&points), "{strategy}");
+        }
+    }
+
+    #[test]
+    fn cancelled_run_keeps_what_it_plotted()
+    {
+        for strategy in Strategy::ALL
+        {
+            let mut scene = scene(strategy);
+            scene.render.points = Some(if strategy == Strategy::Exhaustive { 1 << 14 } else { 1 << 40 });
+
+            let cancel = CancelToken::new();
+            let observer = 
+            {
+                let cancel = cancel.clone();
+                move |p: &Progress| if p.points_done > 0 { cancel.cancel() }
+            };
+            let mut options = RunOptions { observer: Some(Box::new(observer)), report_every: Duration::ZERO, cancel: Some(cancel), ..Default::default() };
+
+            let mut s = Session::<u64>::new(scene);
+            assert_eq!(s.run(&mut options).unwrap(), Outcome::Cancelled, "{strategy}");
+
+            let plotted = s.points_done();
+            assert!(plotted > 0 && plotted < s.scene().render.limit(), "{strategy}");
+            if strategy != Strategy::Exhaustive
+            {
+                // every point lands in the square
+                assert_eq!(s.grid().as_slice().iter().sum::<u64>(), plotted as u64, "{strategy}");
+            }
+        }
+    }
+
+    #[test]
+    fn budget_stops_the_run_on_time()
+    {
+        for strategy in [Strategy::Serial, Strategy::Parallel]
+        {
+            let mut scene = scene(strategy);
+            scene.render.points = None;
+
+            let mut options = RunOptions { budget: Some(Duration::from_millis(100)), ..Default::default() };
+            let mut s = Session::<u64>::new(scene);
+
+            let start = std::time::Instant::now();
+            assert_eq!(s.run(&mut options).unwrap(), Outcome::OutOfTime);
+            assert!(start.elapsed() < Duration::from_secs(2), "{strategy}");
+
+            assert!(s.points_done() > 0);
+            assert_eq!(s.grid().as_slice().iter().sum::<u64>(), s.points_done() as u64);
+        }
+    }
+
+    #[test]
+    fn exhaustive_counts_the_leaves_of_its_tree()
+    {
+        // rounded up to a tree 15 deep
+        let mut s = Session::<u64>::new(scene(Strategy::Exhaustive));
+        assert_eq!(s.points_total(), Some(1 << 15));
+
+        assert_eq!(s.advance(1), (1 << 15) / 256);
+        s.finish();
+        assert_eq!(s.points_done(), 1 << 15);
+
+        // every leaf lands in the square
+        assert_eq!(s.grid().as_slice().iter().sum::<u64>(), 1 << 15);
+    }
+
+    #[test]
+    fn exhaustive_run_stops_inside_a_part()
+    {
+        let mut scene = scene(Strategy::Exhaustive);
+        scene.render.points = Some(1 << 40);
+
+        let mut options = RunOptions { budget: Some(Duration::from_millis(100)), ..Default::default() };
+        let mut s = Session::<u64>::new(scene);
+
+        let start = std::time::Instant::now();
+        assert_eq!(s.run(&mut options).unwrap(), Outcome::OutOfTime);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let state = s.state();
+        assert!(state.parts_done == 0 && state.leaves_done > 0);
+
+        let bytes = s.checkpoint().to_bytes().unwrap();
+        let resumed = Session::<u64>::resume(Checkpoint::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(resumed.state(), state);
+    }
+
+    #[test]
+    fn converges_with_fewer_points_on_smaller_grids()
+    {
+        let converge = |size: usize| -> usize
+        {
+            let mut scene = scene(Strategy::Serial);
+            (scene.render.rows, scene.render.cols, scene.render.points) = (size, size, None);
+
+            let converge = Convergence { tolerance: 0.05, first_snapshot: 1 << 12 };
+            let mut options = RunOptions { converge: Some(converge), ..Default::default() };
+
+            let mut s = Session::<u32>::new(scene);
+            assert_eq!(s.run(&mut options).unwrap(), Outcome::Converged);
+            s.points_done()
+        };
+
+        let (small, large) = (converge(16), converge(128));
+        assert!(small < large, "{small} {large}");
+    }
+
+    #[test]
+    fn workers_stop_mid_slice()
+    {
+        let mut scene = scene(Strategy::Parallel);
+        scene.render.points = Some(1 << 40);
+
+        let cancel = CancelToken::new();
+        let mut s = Session::<u64>::new(scene);
+
+        std::thread::scope(
+            |scope|
+            {
+                scope.spawn(
+                    || 
+                    {
+                        std::thread::sleep(Duration::from_millis(50));
+                        cancel.cancel();
+                    }
+                );
+                s.advance_until(usize::MAX, &cancel);
+            }
+        );
+
+        assert!(s.points_done() > 0);
+        assert_eq!(s.grid().as_slice().iter().sum::<u64>(), s.points_done() as u64);
+    }
+
+    #[test]
+    fn resumed_render_keeps_its_overflow_policy()
+    {
+        let mut scene = scene(Strategy::Serial);
+        scene.render.points = Some(1_000_000);
+
+        let mut s = Session::<u8>::new(scene);
+        let mut options = RunOptions { overflow: Overflow::Fail, budget: Some(Duration::ZERO), ..Default::default() };
+        assert_eq!(s.run(&mut options).unwrap(), Outcome::OutOfTime);
+
+        let mut resumed = Session::<u8>::resume(s.checkpoint()).unwrap();
+        assert_eq!(resumed.overflow(), Overflow::Fail);
+
+        let mut options = RunOptions { overflow: resumed.overflow(), ..Default::default() };
+        assert!(matches!(resumed.run(&mut options), Err(FractalError::Overflow { clipped, .. }) if clipped > 0));
+    }
+
+    #[test]
+    fn resume_rejects_other_pixel_types_and_strategies()
+    {
+        let mut s = Session::<u32>::new(scene(Strategy::Parallel));
+        s.advance(1000);
+        let mut c = s.checkpoint();
+
+        assert!(Session::<u16>::resume(c.clone()).is_err());
+
+        c.scene.render.strategy = Strategy::Serial;
+        assert!(Session::<u32>::resume(c).is_err());
+    }
+}
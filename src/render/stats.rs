@@ -0,0 +1,165 @@
+//! What a render did, for reporting.
+
+use std::{fmt::Display, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{RenderSettings, Strategy};
+use crate::{error::FractalError, my_grid::{dyn_grid::{with_grid, DynGrid}, MyGrid}};
+
+/// Seconds spent in each phase of a render, zero for phases that did not run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Phases
+{
+    pub grid_creation: f64,
+    pub fractalize: f64,
+    /// Turning the counts into an image
+    pub conversion: f64,
+    pub save: f64,
+}
+
+/// Counts of a finished render with the time it took
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderStats
+{
+    pub points: usize,
+    /// Points outside the viewport. Unknown for [`Strategy::Exhaustive`], 
+    /// which plots a weighted hit per pruned subtree rather than one per point.
+    pub off_screen: Option<usize>,
+    /// Pixels at the largest count their type holds
+    pub saturated_pixels: usize,
+    /// Hits dropped on saturated pixels
+    pub clipped_hits: u64,
+    /// Fraction of the pixels hit at least once
+    pub covered_fraction: f64,
+    pub max_hits: u64,
+    /// Mean count of the pixels hit at least once
+    pub mean_hits: f64,
+    pub phases: Phases,
+}
+
+impl RenderStats
+{
+    /// Stats of `grid` with `render.points` plotted into it, or every leaf of 
+    /// [`RenderSettings::tree`] for [`Strategy::Exhaustive`], without timings
+    pub fn of<P>(grid: &MyGrid<P>, render: &RenderSettings) -> Self
+    where
+        P: image::Primitive
+    {
+        let counts = grid.as_slice();
+        let saturated_pixels = counts.iter().filter(|&&c| c == P::max_value()).count();
+
+        let (mut covered, mut sum, mut max_hits) = (0, 0_u64, 0_u64);
+        for c in counts.iter().map(|c| c.to_u64().unwrap_or(0)).filter(|&c| c > 0)
+        {
+            covered += 1;
+            sum = sum.saturating_add(c);
+            max_hits = max_hits.max(c);
+        }
+
+        // without a limit there is nothing to tell the points off screen from
+        let plotted = sum.saturating_add(grid.clipped());
+        let points = match render.strategy
+        {
+            // every leaf of the tree, however many were asked for
+            Strategy::Exhaustive => usize::try_from(render.tree().leaves()).unwrap_or(usize::MAX),
+            _ => render.points.unwrap_or(usize::try_from(plotted).unwrap_or(usize::MAX)),
+        };
+        let off_screen = 
+            (render.strategy != Strategy::Exhaustive && render.points.is_some())
+            .then(|| (points as u64).saturating_sub(plotted) as usize);
+
+        RenderStats
+        {
+            points,
+            off_screen,
+            saturated_pixels,
+            clipped_hits: grid.clipped(),
+            covered_fraction: if counts.is_empty() { 0.0 } else { covered as f64 / counts.len() as f64 },
+            max_hits,
+            mean_hits: if covered > 0 { sum as f64 / covered as f64 } else { 0.0 },
+            phases: Phases::default(),
+        }
+    }
+
+    /// [`RenderStats::of`] for a grid of any pixel type
+    pub fn of_dyn(grid: &DynGrid, render: &RenderSettings) -> Self
+    {
+        with_grid!(grid, g => RenderStats::of(g, render))
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error>
+    {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl Display for RenderStats
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.off_screen
+        {
+            Some(off_screen) => writeln!(f, "points:         {} ({off_screen} off screen)", self.points)?,
+            None => writeln!(f, "points:         {}", self.points)?,
+        }
+        writeln!(f, "covered:        {:.2}% of pixels", self.covered_fraction * 100.0)?;
+        writeln!(f, "hits:           max {}, mean {:.2} per covered pixel", self.max_hits, self.mean_hits)?;
+        writeln!(f, "saturated:      {} pixels, {} hits clipped", self.saturated_pixels, self.clipped_hits)?;
+
+        let p = &self.phases;
+        writeln!(f, "grid creation:  {:.6} s", p.grid_creation)?;
+        writeln!(f, "fractalize:     {:.6} s", p.fractalize)?;
+        writeln!(f, "conversion:     {:.6} s", p.conversion)?;
+        write!(f, "save:           {:.6} s", p.save)
+    }
+}
+
+impl RenderSettings
+{
+    /// [`RenderSettings::render`], also returning its stats with grid creation and fractalize timed
+    pub fn render_with_stats<P>(&self) -> Result<(MyGrid<P>, RenderStats), FractalError>
+    where
+        P: image::Primitive + Default + num_traits::CheckedAdd + Send
+    {
+        let start = Instant::now();
+        let mut grid = self.grid()?;
+        let grid_creation = start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        self.render_into(&mut grid)?;
+        let fractalize = start.elapsed().as_secs_f64();
+
+        let mut stats = RenderStats::of(&grid, self);
+        stats.phases = Phases { grid_creation, fractalize, ..Default::default() };
+        Ok((grid, stats))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::RenderStats;
+    use crate::{my_grid::{dyn_grid::{DynGrid, PixelType}, Viewport}, render::{RenderSettings, Strategy}};
+
+    #[test]
+    fn stats_count_hits_and_off_screen_points()
+    {
+        let settings = RenderSettings { rows: 64, cols: 64, points: Some(50_000), seed: 2, ..Default::default() };
+        let (grid, stats) = settings.render_with_stats::<u32>().unwrap();
+
+        let sum: u64 = grid.as_slice().iter().map(|&c| c as u64).sum();
+        assert_eq!(stats.off_screen, Some(50_000 - sum as usize));
+        assert_eq!(stats.max_hits, *grid.as_slice().iter().max().unwrap() as u64);
+        assert!(stats.covered_fraction > 0.0 && stats.covered_fraction <= 1.0);
+        assert!(stats.phases.fractalize > 0.0);
+
+        let tiny = DynGrid::from_counts(1, 2, Viewport::default(), PixelType::U8, &[300, 0]);
+        let stats = RenderStats::of_dyn(&tiny, &RenderSettings { points: Some(1000), ..Default::default() });
+        assert_eq!((stats.saturated_pixels, stats.clipped_hits, stats.covered_fraction), (1, 45, 0.5));
+        assert_eq!(stats.off_screen, Some(700));
+
+        let exhaustive = RenderSettings { points: Some(1000), strategy: Strategy::Exhaustive, ..Default::default() };
+        let stats = RenderStats::of_dyn(&tiny, &exhaustive);
+        assert_eq!((stats.points, stats.off_screen), (1024, None));
+    }
+}
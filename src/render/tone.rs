@@ -0,0 +1,230 @@
+//! Turning hit counts into pixels.
+//! 
+//! A [`ToneMap`] squashes the counts into `[0, 1]` relative to the 
+//! brightest pixel, then a [`Palette`] picks the color.
+
+use std::{fmt::Display, str::FromStr};
+
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::my_grid::{dyn_grid::{with_grid, DynGrid}, MyGrid};
+
+/// Written as a string in scene files, e.g. `"log"` or `"gamma:2.2"`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ToneMap
+{
+    Linear,
+    Sqrt,
+    /// `ln(1 + v) / ln(1 + max)`, the usual choice for fractal flames
+    #[default]
+    Log,
+    /// `(v / max)^(1 / gamma)`
+    Gamma(f64),
+}
+
+impl ToneMap
+{
+    /// Map a count to `[0, 1]` given the largest count in the image
+    pub fn apply(&self, v: f64, max: f64) -> f64
+    {
+        if max <= 0.0 { return 0.0 }
+
+        let t = match self
+        {
+            ToneMap::Linear => v / max,
+            ToneMap::Sqrt => (v / max).sqrt(),
+            ToneMap::Log => v.ln_1p() / max.ln_1p(),
+            ToneMap::Gamma(g) => (v / max).powf(1.0 / g),
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+impl TryFrom<String> for ToneMap
+{
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ToneMap> for String
+{
+    fn from(value: ToneMap) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for ToneMap
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self
+        {
+            ToneMap::Linear => write!(f, "linear"),
+            ToneMap::Sqrt => write!(f, "sqrt"),
+            ToneMap::Log => write!(f, "log"),
+            ToneMap::Gamma(g) => write!(f, "gamma:{g}"),
+        }
+    }
+}
+
+impl FromStr for ToneMap
+{
+    type Err = String;
+
+    /// One of `linear`, `sqrt`, `log` or `gamma:<g>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        match s.split_once(':')
+        {
+            None if s == "linear" => Ok(ToneMap::Linear),
+            None if s == "sqrt" => Ok(ToneMap::Sqrt),
+            None if s == "log" => Ok(ToneMap::Log),
+            Some(("gamma", g)) => 
+            {
+                match g.parse::<f64>()
+                {
+                    Ok(g) if g > 0.0 && g.is_finite() => Ok(ToneMap::Gamma(g)),
+                    _ => Err(format!("gamma must be a positive number, found '{g}'")),
+                }
+            },
+            _ => Err(format!("unknown tone map '{s}', expected one of linear, sqrt, log, gamma:<g>")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette
+{
+    #[default]
+    Grey,
+    /// black, red, yellow, white
+    Fire,
+    /// black, blue, cyan, white
+    Ice,
+    /// dark purple through teal to yellow, approximating matplotlib's viridis
+    Viridis,
+}
+
+impl Palette
+{
+    pub const ALL: [Palette; 4] = [Palette::Grey, Palette::Fire, Palette::Ice, Palette::Viridis];
+
+    fn stops(&self) -> &'static [[u8; 3]]
+    {
+        match self
+        {
+            Palette::Grey => &[[0, 0, 0], [255, 255, 255]],
+            Palette::Fire => &[[0, 0, 0], [200, 30, 0], [255, 200, 0], [255, 255, 255]],
+            Palette::Ice => &[[0, 0, 0], [0, 40, 200], [0, 220, 255], [255, 255, 255]],
+            Palette::Viridis => &[[68, 1, 84], [59, 82, 139], [33, 145, 140], [94, 201, 98], [253, 231, 37]],
+        }
+    }
+
+    /// Color for `t` in `[0, 1]`, interpolating linearly between the stops
+    pub fn color(&self, t: f64) -> Rgb<u8>
+    {
+        let stops = self.stops();
+        let pos = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let i = (pos as usize).min(stops.len() - 2);
+        let frac = pos - i as f64;
+
+        let (a, b) = (stops[i], stops[i + 1]);
+        Rgb(
+            [0, 1, 2].map(|k| (a[k] as f64 + (b[k] as f64 - a[k] as f64) * frac).round() as u8)
+        )
+    }
+}
+
+impl Display for Palette
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self
+        {
+            Palette::Grey => "grey",
+            Palette::Fire => "fire",
+            Palette::Ice => "ice",
+            Palette::Viridis => "viridis",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Palette
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Palette::ALL
+        .into_iter()
+        .find(|k| k.to_string() == s.to_ascii_lowercase())
+        .ok_or_else(|| format!("unknown palette '{s}', expected one of grey, fire, ice, viridis"))
+    }
+}
+
+/// Tone map and color a grid. Grey renders come out as 8-bit luma, everything else as 8-bit RGB.
+pub fn colorize<P>(grid: &MyGrid<P>, tone: ToneMap, palette: Palette) -> DynamicImage
+where
+    P: image::Primitive
+{
+    let counts = grid.as_slice();
+    let max = counts.iter().map(|p| p.to_f64().unwrap_or(0.0)).fold(0.0, f64::max);
+    let (w, h) = (grid.cols() as u32, grid.rows() as u32);
+
+    let t = |i: usize| tone.apply(counts[i].to_f64().unwrap_or(0.0), max);
+
+    match palette
+    {
+        Palette::Grey => 
+        {
+            DynamicImage::ImageLuma8(
+                GrayImage::from_fn(w, h, |x, y| Luma([(t(y as usize * w as usize + x as usize) * 255.0).round() as u8]))
+            )
+        },
+        _ =>
+        {
+            DynamicImage::ImageRgb8(
+                RgbImage::from_fn(w, h, |x, y| palette.color(t(y as usize * w as usize + x as usize)))
+            )
+        },
+    }
+}
+
+/// [`colorize`] for a grid of any pixel type
+pub fn colorize_dyn(grid: &DynGrid, tone: ToneMap, palette: Palette) -> DynamicImage
+{
+    with_grid!(grid, g => colorize(g, tone, palette))
+}
+
+#[cfg(test)]
+mod test
+{
+    use image::Rgb;
+
+    use super::{Palette, ToneMap};
+
+    #[test]
+    fn tone_maps_hit_the_ends()
+    {
+        for tone in [ToneMap::Linear, ToneMap::Sqrt, ToneMap::Log, ToneMap::Gamma(2.2)]
+        {
+            assert_eq!(tone.apply(0.0, 100.0), 0.0, "{tone}");
+            assert_eq!(tone.apply(100.0, 100.0), 1.0, "{tone}");
+            assert_eq!(tone.to_string().parse::<ToneMap>(), Ok(tone));
+        }
+        assert_eq!(ToneMap::Log.apply(5.0, 0.0), 0.0);
+        assert!("gamma:-1".parse::<ToneMap>().is_err());
+    }
+
+    #[test]
+    fn palette_ends_are_the_stops()
+    {
+        assert_eq!(Palette::Fire.color(0.0), Rgb([0, 0, 0]));
+        assert_eq!(Palette::Fire.color(1.0), Rgb([255, 255, 255]));
+        assert_eq!(Palette::Grey.color(0.5), Rgb([128, 128, 128]));
+    }
+}